@@ -1,4 +1,6 @@
-from configcrunch._main import YamlConfigDocument, DocReference, load_multiple_yml, \
+from configcrunch._main import YamlConfigDocument, DocReference, load_multiple_yml, list_documents, \
+    lint_documents, register_document_type, registered_document_types, load_any, \
+    load_all, register_tag_handler, \
     ConfigcrunchError, ReferencedDocumentNotFound, CircularDependencyError, \
     VariableProcessingError, InvalidDocumentError, InvalidHeaderError, InvalidRemoveError
 
@@ -37,6 +39,13 @@ __all__ = [
     'DocReference',
     'variable_helper',
     'load_multiple_yml',
+    'list_documents',
+    'lint_documents',
+    'register_document_type',
+    'registered_document_types',
+    'load_any',
+    'load_all',
+    'register_tag_handler',
 
     'ConfigcrunchError',
     'ReferencedDocumentNotFound',
@@ -0,0 +1,58 @@
+"""
+Command line interface.
+
+Lint all documents of the given types in the given lookup paths::
+
+    configcrunch lint -t mypackage.documents.Service -t mypackage.documents.Project -l ./repo1 -l ./repo2
+"""
+import argparse
+import importlib
+import sys
+from typing import List, Optional, Type
+
+from configcrunch import YamlConfigDocument, lint_documents
+
+
+def _import_type(dotted_name: str) -> Type[YamlConfigDocument]:
+    module_name, _, class_name = dotted_name.rpartition('.')
+    if module_name == '':
+        raise argparse.ArgumentTypeError(f"{dotted_name} is not a fully qualified class name.")
+    try:
+        doc_type = getattr(importlib.import_module(module_name), class_name)
+    except (ImportError, AttributeError) as e:
+        raise argparse.ArgumentTypeError(f"Unable to import {dotted_name}: {e}") from e
+    if not (isinstance(doc_type, type) and issubclass(doc_type, YamlConfigDocument)):
+        raise argparse.ArgumentTypeError(f"{dotted_name} is not a YamlConfigDocument class.")
+    return doc_type
+
+
+def lint(doc_types: List[Type[YamlConfigDocument]], lookup_paths: List[str]) -> int:
+    failures = lint_documents(doc_types, lookup_paths)
+    for ref_path, files, error in failures:
+        print(f"{', '.join(files)} ({ref_path}): {error.__class__.__name__}: {error}", file=sys.stderr)
+    print(f"{len(failures)} invalid document(s) found.")
+    return 1 if failures else 0
+
+
+def main(argv: Optional[List[str]] = None) -> int:
+    parser = argparse.ArgumentParser(prog='configcrunch')
+    subparsers = parser.add_subparsers(dest='command', required=True)
+
+    lint_parser = subparsers.add_parser('lint', help='Validate all documents in the lookup paths.')
+    lint_parser.add_argument(
+        '-t', '--type', dest='types', action='append', required=True, type=_import_type,
+        help='Fully qualified name of a YamlConfigDocument class to lint documents of. Can be repeated.'
+    )
+    lint_parser.add_argument(
+        '-l', '--lookup-path', dest='lookup_paths', action='append', required=True,
+        help='Lookup path (repository) to lint. Can be repeated.'
+    )
+
+    args = parser.parse_args(argv)
+    if args.command == 'lint':
+        return lint(args.types, args.lookup_paths)
+    return 2
+
+
+if __name__ == '__main__':
+    sys.exit(main())
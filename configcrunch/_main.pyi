@@ -2,7 +2,7 @@
 from __future__ import annotations
 
 from abc import abstractmethod
-from typing import List, Callable, Type, Optional, Union, TypeVar, final, Tuple, Any, ContextManager, Self
+from typing import List, Dict, Callable, Type, Optional, Union, TypeVar, final, Tuple, Any, ContextManager, Self, IO
 
 from schema import Schema
 
@@ -22,6 +22,15 @@ class InvalidRemoveError(InvalidDocumentError): ...
 
 
 def load_multiple_yml(doc_type: Type[T], *in_args: str) -> T: ...
+def list_documents(doc_type: Type[YamlConfigDocument], lookup_paths: List[str]) -> List[Tuple[str, List[str]]]: ...
+def lint_documents(
+        doc_types: List[Type[YamlConfigDocument]], lookup_paths: List[str]
+) -> List[Tuple[str, List[str], Exception]]: ...
+def register_document_type(doc_type: Type[T]) -> Type[T]: ...
+def registered_document_types() -> Dict[str, Type[YamlConfigDocument]]: ...
+def load_any(path_to_yaml: str) -> YamlConfigDocument: ...
+def load_all(path_to_yaml: str) -> Dict[str, YamlConfigDocument]: ...
+def register_tag_handler(tag: str, handler: Callable[[Any, str], Any]) -> None: ...
 
 
 class YamlConfigDocument:
@@ -42,7 +51,13 @@ class YamlConfigDocument:
 
     @classmethod
     @final
-    def from_yaml(cls, path_to_yaml: str) -> Self: ...
+    def from_yaml(cls, path_to_yaml: str, stream_index: Optional[int] = None) -> Self: ...
+    @classmethod
+    @final
+    def from_yaml_str(cls, text: str, origin_name: Optional[str] = None, stream_index: Optional[int] = None) -> Self: ...
+    @classmethod
+    @final
+    def from_yaml_stream(cls, stream: IO, origin_name: Optional[str] = None, stream_index: Optional[int] = None) -> Self: ...
     @classmethod
     @final
     def from_dict(cls, dict: dict) -> Self: ...
@@ -52,14 +67,32 @@ class YamlConfigDocument:
     @abstractmethod
     def header(cls) -> str: ...
     @classmethod
+    def header_aliases(cls) -> List[str]: ...
+    @classmethod
+    def headerless(cls) -> bool: ...
+    @classmethod
+    def reject_non_string_keys(cls) -> bool: ...
+    @classmethod
+    def yaml11_booleans(cls) -> bool:
+        """
+        If true, the unquoted YAML 1.1 booleans yes, no, on and off (lowercase, capitalized or
+        uppercase) are loaded as booleans when loading YAML-documents. Otherwise they are strings.
+        """
+        ...
+    @classmethod
     @abstractmethod
     def schema(cls) -> Schema: ...
     @classmethod
     @abstractmethod
-    def subdocuments(cls) -> List[Tuple[str, Type[YamlConfigDocument]]]: ...
+    def subdocuments(cls) -> List[Union[
+        Tuple[str, Type[YamlConfigDocument]],
+        Tuple[str, Type[YamlConfigDocument], Dict[str, Optional[str]]],
+        Tuple[str, str, Dict[str, Type[YamlConfigDocument]]],
+        Tuple[str, str, Dict[str, Type[YamlConfigDocument]], Dict[str, Optional[str]]],
+    ]]: ...
     def validate(self) -> bool: ...
     @final
-    def resolve_and_merge_references(self, lookup_paths: List[str]) -> Self: ...
+    def resolve_and_merge_references(self, lookup_paths: List[str], ref_context: Optional[dict] = None) -> Self: ...
     @final
     def process_vars(self) -> Self: ...
     def process_vars_for(self, target: str, additional_helpers: List[Callable]|None = None) -> str: ...
@@ -75,6 +108,12 @@ class YamlConfigDocument:
     def __iter__(self): ...
     def items(self): ...
     def to_dict(self): ...
+    def to_yaml(self, header: bool = True, subdocument_tags: bool = False, sort_keys: bool = False) -> str: ...
+    def to_json(self, header: bool = True, sort_keys: bool = False, indent: Optional[int] = None) -> str: ...
+    def to_toml(self, header: bool = True, sort_keys: bool = False) -> str: ...
+    def export(self, path: str) -> None: ...
+    def diff_to_override(self, reference: str, lookup_paths: List[str]) -> str: ...
+    def set_in_source(self, key_path: List[Union[str, int]], value: Any, source: Optional[str] = None) -> str: ...
     def internal_get(self, key: str) -> Any: ...
     def internal_set(self, key: str, val: Any): ...
     def internal_contains(self, key: str) -> bool: ...
@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+use std::mem::take;
+
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use saphyr_parser::{Event, Parser, ScalarStyle};
+use serde_yaml::{Mapping, Value};
+
+use crate::InvalidDocumentError;
+use crate::conv::{SimpleYcdValueType, scalar_key_to_string};
+
+/// Plain scalars that are booleans in YAML 1.1 but strings in YAML 1.2 (the same set as PyYAML).
+const YAML11_TRUE: [&str; 6] = ["yes", "Yes", "YES", "on", "On", "ON"];
+const YAML11_FALSE: [&str; 6] = ["no", "No", "NO", "off", "Off", "OFF"];
+
+/// Options for loading YAML documents of a document type, see the class methods
+/// reject_non_string_keys and yaml11_booleans of YamlConfigDocument.
+#[derive(Default)]
+pub(crate) struct LoaderOptions {
+    /// The type of the loaded documents. Tags with the name of it or its sub-document types are removed.
+    pub(crate) doc_type: Option<Py<PyType>>,
+    /// Whether mapping keys that are not strings raise an error instead of being converted.
+    pub(crate) reject_non_string_keys: bool,
+    /// Whether unquoted YAML 1.1 booleans (yes, no, on, off) are loaded as booleans.
+    pub(crate) yaml11_booleans: bool,
+}
+
+impl LoaderOptions {
+    /// Returns the options of the given YamlConfigDocument type.
+    pub(crate) fn of(doc_type: &Bound<PyType>) -> PyResult<Self> {
+        Ok(Self {
+            doc_type: Some(doc_type.clone().unbind()),
+            reject_non_string_keys: doc_type.call_method0("reject_non_string_keys")?.extract()?,
+            yaml11_booleans: doc_type.call_method0("yaml11_booleans")?.extract()?,
+        })
+    }
+}
+
+/// If YAML 1.1 booleans are enabled, rewrites all unquoted and untagged YAML 1.1 booleans
+/// in content to true or false. Otherwise, or if there are none, content is returned as is.
+///
+/// If content can not be parsed it is also returned as is; the error is reported
+/// when it is loaded.
+pub(crate) fn apply_yaml11_booleans<'a>(content: &'a str, options: &LoaderOptions) -> Cow<'a, str> {
+    if !options.yaml11_booleans {
+        return Cow::Borrowed(content);
+    }
+    // (start, end, value) of the scalars to replace, as char indices.
+    let mut replacements = Vec::new();
+    for event in Parser::new_from_str(content) {
+        let Ok((Event::Scalar(value, ScalarStyle::Plain, _, None), span)) = event else {
+            continue;
+        };
+        let replacement = if YAML11_TRUE.contains(&value.as_ref()) {
+            "true"
+        } else if YAML11_FALSE.contains(&value.as_ref()) {
+            "false"
+        } else {
+            continue;
+        };
+        replacements.push((span.start.index(), span.end.index(), replacement));
+    }
+    if replacements.is_empty() {
+        return Cow::Borrowed(content);
+    }
+    let mut result = String::with_capacity(content.len());
+    let mut replacements = replacements.into_iter().peekable();
+    let mut skip_until = 0;
+    for (idx, c) in content.chars().enumerate() {
+        if let Some((start, end, replacement)) = replacements.peek() {
+            if idx == *start {
+                result.push_str(replacement);
+                skip_until = *end;
+                replacements.next();
+            }
+        }
+        if idx >= skip_until {
+            result.push(c);
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Converts all mapping keys in value, that are not strings, to strings (see
+/// scalar_key_to_string) or raises an InvalidDocumentError if non-string keys are rejected.
+/// Keys that are lists or mappings are never supported.
+pub(crate) fn normalize_keys(
+    value: &mut Value,
+    origin: &str,
+    options: &LoaderOptions,
+) -> PyResult<()> {
+    match value {
+        Value::Mapping(mapping) => {
+            if mapping.keys().any(|k| !k.is_string()) {
+                let mut normalized = Mapping::with_capacity(mapping.len());
+                for (k, v) in take(mapping) {
+                    let k = normalize_key(k, origin, options)?;
+                    if normalized.contains_key(&k) {
+                        return Err(InvalidDocumentError::new_err(format!(
+                            "Duplicate key '{}' in {} after converting keys to strings.",
+                            k.as_str().unwrap_or_default(),
+                            origin
+                        )));
+                    }
+                    normalized.insert(k, v);
+                }
+                *mapping = normalized;
+            }
+            for v in mapping.values_mut() {
+                normalize_keys(v, origin, options)?;
+            }
+        }
+        Value::Sequence(sequence) => {
+            for v in sequence {
+                normalize_keys(v, origin, options)?;
+            }
+        }
+        Value::Tagged(tagged) => normalize_keys(&mut tagged.value, origin, options)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn normalize_key(key: Value, origin: &str, options: &LoaderOptions) -> PyResult<Value> {
+    if key.is_string() {
+        return Ok(key);
+    }
+    let invalid = |reason: &str| {
+        InvalidDocumentError::new_err(format!(
+            "Invalid key '{}' in {}: {}",
+            serde_yaml::to_string(&key).unwrap_or_default().trim_end(),
+            origin,
+            reason
+        ))
+    };
+    if options.reject_non_string_keys {
+        return Err(invalid("Keys must be strings."));
+    }
+    serde_yaml::from_value::<SimpleYcdValueType>(key.clone())
+        .ok()
+        .and_then(|k| scalar_key_to_string(&k))
+        .map(Value::String)
+        .ok_or_else(|| invalid("Keys must be strings, numbers, booleans or null."))
+}
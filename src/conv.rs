@@ -1,6 +1,6 @@
-use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 
+use indexmap::IndexMap;
 use pyo3::prelude::*;
 use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString};
 use pyo3::{IntoPyObjectExt, exceptions};
@@ -9,12 +9,12 @@ use serde::{Deserialize, Serialize, Serializer};
 use crate::YamlConfigDocument;
 use crate::pyutil::ClonePyRef;
 
-pub(crate) type YcdDict = HashMap<String, YcdValueType>;
+pub(crate) type YcdDict = IndexMap<String, YcdValueType>;
 pub(crate) type YcdList = Vec<YcdValueType>;
 #[derive(Debug)]
-pub(crate) struct YHashMap<K, V>(pub(crate) HashMap<K, V>);
+pub(crate) struct YIndexMap<K, V>(pub(crate) IndexMap<K, V>);
 
-impl<K: Debug, V: Debug> Display for YHashMap<K, V> {
+impl<K: Debug, V: Debug> Display for YIndexMap<K, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
@@ -140,7 +140,7 @@ impl YcdValueType {
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(untagged)]
 pub(crate) enum SimpleYcdValueType {
-    Dict(HashMap<String, SimpleYcdValueType>),
+    Dict(IndexMap<String, SimpleYcdValueType>),
     List(Vec<SimpleYcdValueType>),
     YString(String),
     Bool(bool),
@@ -189,9 +189,16 @@ impl<'py> FromPyObject<'_, 'py> for YcdValueType {
         }
         match v.get_type().name()?.to_str()? {
             "dict" => {
-                if let Ok(v) = <HashMap<String, YcdValueType>>::extract(v) {
+                if let Ok(v) = <IndexMap<String, YcdValueType>>::extract(v) {
                     return Ok(YcdValueType::Dict(v));
                 }
+                if let Ok(v) = v.cast::<PyDict>() {
+                    return v
+                        .iter()
+                        .map(|(k, v)| Ok((py_key_to_string(k)?, v.extract()?)))
+                        .collect::<PyResult<_>>()
+                        .map(YcdValueType::Dict);
+                }
             }
             "list" => {
                 if let Ok(v) = <Vec<YcdValueType>>::extract(v) {
@@ -233,7 +240,7 @@ impl<'py> FromPyObject<'_, 'py> for YcdValueType {
             Ok(YcdValueType::Bool(v))
         } else if let Ok(v) = <Vec<YcdValueType>>::extract(v) {
             Ok(YcdValueType::List(v))
-        } else if let Ok(v) = <HashMap<String, YcdValueType>>::extract(v) {
+        } else if let Ok(v) = <IndexMap<String, YcdValueType>>::extract(v) {
             Ok(YcdValueType::Dict(v))
         } else {
             Err(exceptions::PyTypeError::new_err(format!(
@@ -305,40 +312,70 @@ impl From<YcdValueType> for SimpleYcdValueType {
     }
 }
 
-impl From<YHashMap<String, SimpleYcdValueType>> for HashMap<String, YcdValueType> {
-    fn from(h: YHashMap<String, SimpleYcdValueType>) -> Self {
+impl From<YIndexMap<String, SimpleYcdValueType>> for IndexMap<String, YcdValueType> {
+    fn from(h: YIndexMap<String, SimpleYcdValueType>) -> Self {
         h.0.into_iter().map(|(k, v)| (k, v.into())).collect()
     }
 }
 
 #[inline]
-pub(crate) fn pyany_to_simple_ycd(v: Bound<PyAny>) -> SimpleYcdValueType {
-    if let Ok(v) = v.extract::<Bound<PyDict>>() {
-        SimpleYcdValueType::from(v)
+pub(crate) fn pyany_to_simple_ycd(v: Bound<PyAny>) -> PyResult<SimpleYcdValueType> {
+    // None and bool must be checked before int, since bool is a subclass of int.
+    Ok(if v.is_none() {
+        SimpleYcdValueType::Null
+    } else if let Ok(v) = v.extract::<Bound<PyDict>>() {
+        SimpleYcdValueType::Dict(
+            v.into_iter()
+                .map(|(k, v)| Ok((py_key_to_string(k)?, pyany_to_simple_ycd(v)?)))
+                .collect::<PyResult<_>>()?,
+        )
     } else if let Ok(v) = v.extract::<Bound<PyString>>() {
         SimpleYcdValueType::from(v)
+    } else if let Ok(v) = v.extract::<Bound<PyBool>>() {
+        SimpleYcdValueType::from(v)
     } else if let Ok(v) = v.extract::<Bound<PyInt>>() {
         SimpleYcdValueType::from(v)
     } else if let Ok(v) = v.extract::<Bound<PyFloat>>() {
         SimpleYcdValueType::from(v)
     } else if let Ok(v) = v.extract::<Bound<PyList>>() {
-        SimpleYcdValueType::from(v)
-    } else if let Ok(v) = v.extract::<Bound<PyBool>>() {
-        SimpleYcdValueType::from(v)
+        SimpleYcdValueType::List(
+            v.into_iter()
+                .map(pyany_to_simple_ycd)
+                .collect::<PyResult<_>>()?,
+        )
     } else {
         // TODO: Support more?
         SimpleYcdValueType::Bool(false)
+    })
+}
+
+/// Converts a scalar mapping key to a string. Keys loaded from YAML and keys of Python
+/// dicts are converted the same way: Numbers as in YAML, booleans to true / false and
+/// null / None to null. Returns None for lists and dicts.
+pub(crate) fn scalar_key_to_string(key: &SimpleYcdValueType) -> Option<String> {
+    match key {
+        SimpleYcdValueType::YString(v) => Some(v.clone()),
+        SimpleYcdValueType::Bool(v) => Some(v.to_string()),
+        SimpleYcdValueType::Int(v) => Some(v.to_string()),
+        SimpleYcdValueType::Float(v) => Some(serde_yaml::Number::from(*v).to_string()),
+        SimpleYcdValueType::Null => Some("null".to_string()),
+        SimpleYcdValueType::Dict(_) | SimpleYcdValueType::List(_) => None,
     }
 }
 
-impl From<Bound<'_, PyDict>> for SimpleYcdValueType {
-    fn from(v: Bound<PyDict>) -> Self {
-        SimpleYcdValueType::Dict(
-            v.into_iter()
-                .map(|(k, v)| (pyany_to_simple_ycd(k).to_string(), pyany_to_simple_ycd(v)))
-                .collect(),
-        )
+/// Converts the key of a Python dict to a string, see scalar_key_to_string.
+/// Other keys (eg. tuples) are converted with str().
+fn py_key_to_string(key: Bound<PyAny>) -> PyResult<String> {
+    if key.is_none()
+        || key.is_instance_of::<PyString>()
+        || key.is_instance_of::<PyInt>()
+        || key.is_instance_of::<PyFloat>()
+    {
+        if let Some(key) = scalar_key_to_string(&pyany_to_simple_ycd(key.clone())?) {
+            return Ok(key);
+        }
     }
+    Ok(key.str()?.to_string())
 }
 
 impl From<Bound<'_, PyString>> for SimpleYcdValueType {
@@ -359,12 +396,6 @@ impl From<Bound<'_, PyFloat>> for SimpleYcdValueType {
     }
 }
 
-impl From<Bound<'_, PyList>> for SimpleYcdValueType {
-    fn from(v: Bound<PyList>) -> Self {
-        SimpleYcdValueType::List(v.into_iter().map(pyany_to_simple_ycd).collect())
-    }
-}
-
 impl From<Bound<'_, PyBool>> for SimpleYcdValueType {
     fn from(v: Bound<PyBool>) -> Self {
         SimpleYcdValueType::Bool(v.is_true())
@@ -0,0 +1,225 @@
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use serde_yaml::{Mapping, Sequence, Value};
+
+use crate::conv::YcdDict;
+use crate::conv::YcdValueType::YString;
+use crate::dump::document_value;
+use crate::merger::SubdocSpec;
+use crate::{REF, REMOVE, REMOVE_FROM_LIST_PREFIX, YamlConfigDocument};
+
+/// Returns the smallest document (as YAML, with header) that results in the current content of doc,
+/// when it references the document at reference and is merged with it.
+///
+/// The referenced document is loaded as a document of the same type and its references are
+/// resolved with lookup_paths. Raises a ValueError if a list of doc can not be the result of
+/// merging (see diff_list).
+pub(crate) fn diff_to_override(
+    doc: &Bound<YamlConfigDocument>,
+    reference: &str,
+    lookup_paths: Vec<String>,
+) -> PyResult<String> {
+    let header: String = doc.call_method0("header")?.extract()?;
+    let dict = YcdDict::from([(REF.to_string(), YString(reference.to_string()))]);
+    let base = doc.get_type().call_method1("from_dict", (dict,))?;
+    // Relative references are resolved relative to the document.
+    base.setattr("path", doc.borrow().path.clone())?;
+    base.call_method1("resolve_and_merge_references", (lookup_paths,))?;
+
+    let base = document_value(base.cast()?)?;
+    let result = document_value(doc)?;
+    let mut body = Mapping::from_iter([(Value::from(REF), Value::from(reference))]);
+    let scope = Scope::of(&doc.get_type(), 0)?;
+    if let Some(Value::Mapping(diff)) = diff(&base, &result, &mut vec![], reference, &scope)? {
+        body.extend(diff);
+    }
+    let body = Value::Mapping(Mapping::from_iter([(
+        Value::from(header),
+        Value::Mapping(body),
+    )]));
+    serde_yaml::to_string(&body).map_err(|e| {
+        exceptions::PyValueError::new_err(format!("Unable to convert the document to YAML: {}", e))
+    })
+}
+
+/// Returns the value, that results in result if it is merged on top of base, or None if they
+/// are equal. Dicts are compared key by key; keys missing in result are set to $remove.
+fn diff(
+    base: &Value,
+    result: &Value,
+    path: &mut Vec<String>,
+    reference: &str,
+    scope: &Scope<'_>,
+) -> PyResult<Option<Value>> {
+    match (base, result) {
+        (Value::Mapping(base), Value::Mapping(result)) => {
+            let mut changes = Mapping::new();
+            for (k, v) in result {
+                path.push(key_name(k));
+                let change = match base.get(k) {
+                    Some(base_v) => {
+                        let inner = scope.enter(v, path)?;
+                        diff(base_v, v, path, reference, inner.as_ref().unwrap_or(scope))?
+                    }
+                    None => Some(without_injected_keys(k, false, v, path, scope)?),
+                };
+                path.pop();
+                if let Some(change) = change {
+                    changes.insert(k.clone(), change);
+                }
+            }
+            for k in base.keys().filter(|k| !result.contains_key(*k)) {
+                changes.insert(k.clone(), Value::from(REMOVE));
+            }
+            Ok((!changes.is_empty()).then_some(Value::Mapping(changes)))
+        }
+        (Value::Sequence(base), Value::Sequence(result)) => {
+            diff_list(base, result, path, reference, scope)
+        }
+        _ if base == result => Ok(None),
+        _ => Ok(Some(result.clone())),
+    }
+}
+
+/// Merging appends the entries of a list to the list of the referenced document and only strings
+/// can be removed (with $remove:: entries, which remove all equal strings). So result must start
+/// with the entries of base, without the strings that are not in result, followed by the new
+/// entries.
+fn diff_list(
+    base: &Sequence,
+    result: &Sequence,
+    path: &mut Vec<String>,
+    reference: &str,
+    scope: &Scope<'_>,
+) -> PyResult<Option<Value>> {
+    let mut removed: Vec<&str> = Vec::new();
+    for v in base {
+        if let Value::String(s) = v {
+            if !result.contains(v) && !removed.contains(&s.as_str()) {
+                removed.push(s);
+            }
+        }
+    }
+    let kept: Vec<&Value> = base
+        .iter()
+        .filter(|v| !matches!(v, Value::String(s) if removed.contains(&s.as_str())))
+        .collect();
+    if result.len() < kept.len() || kept.iter().zip(result).any(|(k, r)| *k != r) {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "Unable to express the list at {} as an override of {}: \
+            Lists can only be extended and strings can only be removed from them.",
+            path.join("."),
+            reference
+        )));
+    }
+    let mut changes: Sequence = removed
+        .into_iter()
+        .map(|s| Value::from(format!("{}{}", REMOVE_FROM_LIST_PREFIX, s)))
+        .collect();
+    for (i, v) in result.iter().enumerate().skip(kept.len()) {
+        path.push(i.to_string());
+        changes.push(without_injected_keys(
+            &Value::from(i),
+            true,
+            v,
+            path,
+            scope,
+        )?);
+        path.pop();
+    }
+    Ok((!changes.is_empty()).then_some(Value::Sequence(changes)))
+}
+
+/// Returns the value at path without the keys, that were inserted into it and into the
+/// subdocuments in it (the dict key or list index, see the subdocument options). They are
+/// inserted again when the override is loaded. position is the dict key or list index of value.
+fn without_injected_keys(
+    position: &Value,
+    in_list: bool,
+    value: &Value,
+    path: &mut Vec<String>,
+    scope: &Scope<'_>,
+) -> PyResult<Value> {
+    let inner = scope.enter(value, path)?;
+    let mut value = value.clone();
+    if let (Some(spec), Value::Mapping(mapping)) = (scope.spec_at(path)?, &mut value) {
+        if let Some(key) = spec.injected_key(in_list) {
+            if mapping.get(key) == Some(position) {
+                mapping.shift_remove(key);
+            }
+        }
+    }
+    let scope = inner.as_ref().unwrap_or(scope);
+    match &mut value {
+        Value::Mapping(mapping) => {
+            for (k, v) in mapping.iter_mut() {
+                path.push(key_name(k));
+                *v = without_injected_keys(k, false, v, path, scope)?;
+                path.pop();
+            }
+        }
+        Value::Sequence(sequence) => {
+            for (i, v) in sequence.iter_mut().enumerate() {
+                path.push(i.to_string());
+                *v = without_injected_keys(&Value::from(i), true, v, path, scope)?;
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+    Ok(value)
+}
+
+/// The subdocument specs of the document, whose content is at path[start..].
+struct Scope<'py> {
+    py: Python<'py>,
+    specs: Vec<SubdocSpec>,
+    start: usize,
+}
+
+impl<'py> Scope<'py> {
+    fn of(typ: &Bound<'py, PyType>, start: usize) -> PyResult<Self> {
+        Ok(Self {
+            py: typ.py(),
+            specs: typ.call_method0("subdocuments")?.extract()?,
+            start,
+        })
+    }
+
+    /// Returns the spec of the subdocument at path, if there is one.
+    fn spec_at(&self, path: &[String]) -> PyResult<Option<&SubdocSpec>> {
+        for spec in &self.specs {
+            if spec.matches(&path[self.start..])? {
+                return Ok(Some(spec));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the scope of the subdocument with the content value at path, if there is one.
+    /// Subdocuments of unknown type have no subdocuments.
+    fn enter(&self, value: &Value, path: &[String]) -> PyResult<Option<Scope<'py>>> {
+        let Some(spec) = self.spec_at(path)? else {
+            return Ok(None);
+        };
+        match spec.value_type(value) {
+            Some(typ) => Self::of(typ.bind(self.py), path.len()).map(Some),
+            None => Ok(Some(Self {
+                py: self.py,
+                specs: Vec::new(),
+                start: path.len(),
+            })),
+        }
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs::write;
+
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use serde::ser::{Error, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_yaml::value::{Tag, TaggedValue};
+
+use crate::conv::{YcdDict, YcdValueType};
+use crate::pyutil::ClonePyRef;
+use crate::{REF, YamlConfigDocument};
+
+/// Options for dumping documents with to_yaml, to_json and to_toml.
+pub(crate) struct DumpOptions {
+    /// Whether the document is dumped under its header.
+    pub(crate) header: bool,
+    /// Whether sub-documents are tagged with the name of their class (eg. !Level). Only for YAML.
+    pub(crate) subdocument_tags: bool,
+    /// Whether the keys of all dicts are sorted.
+    pub(crate) sort_keys: bool,
+}
+
+/// Serializes a value of a document with the given options. Sub-documents are serialized with
+/// their current content: If they are frozen, the frozen doc is used, otherwise the internal one.
+struct Dump<'a> {
+    value: &'a YcdValueType,
+    options: &'a DumpOptions,
+}
+
+impl Serialize for Dump<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.value {
+            YcdValueType::Ycd(doc) => Python::attach(|py| {
+                let content = document_content(doc.0.bind(py)).map_err(S::Error::custom)?;
+                let content = YcdValueType::Dict(content);
+                let content = Dump {
+                    value: &content,
+                    options: self.options,
+                };
+                if self.options.subdocument_tags {
+                    let name = doc.0.bind(py).get_type().name().map_err(S::Error::custom)?;
+                    TaggedValue {
+                        tag: Tag::new(name.to_string()),
+                        value: serde_yaml::to_value(&content).map_err(S::Error::custom)?,
+                    }
+                    .serialize(serializer)
+                } else {
+                    content.serialize(serializer)
+                }
+            }),
+            YcdValueType::Dict(dict) => {
+                let mut entries: Vec<(&String, &YcdValueType)> = dict.iter().collect();
+                if self.options.sort_keys {
+                    entries.sort_by_key(|(k, _)| *k);
+                }
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(
+                        k,
+                        &Dump {
+                            value: v,
+                            options: self.options,
+                        },
+                    )?;
+                }
+                map.end()
+            }
+            YcdValueType::List(list) => {
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for v in list {
+                    seq.serialize_element(&Dump {
+                        value: v,
+                        options: self.options,
+                    })?;
+                }
+                seq.end()
+            }
+            scalar => scalar.serialize(serializer),
+        }
+    }
+}
+
+/// Returns the current content of the document: The frozen doc if it is frozen, otherwise the
+/// internal doc.
+fn document_content(doc: &Bound<YamlConfigDocument>) -> PyResult<YcdDict> {
+    let borrow = doc.borrow();
+    match &borrow.frozen {
+        Some(frozen) => frozen.extract(doc.py()),
+        None => Ok(borrow.doc.clone_pyref(doc.py())),
+    }
+}
+
+/// Serializes the document with serialize, after applying the options.
+fn dump_document<T>(
+    doc: &Bound<YamlConfigDocument>,
+    options: &DumpOptions,
+    format: &str,
+    serialize: impl FnOnce(&Dump) -> Result<T, String>,
+) -> PyResult<T> {
+    let mut value = YcdValueType::Dict(document_content(doc)?);
+    if options.header {
+        let header: String = doc.call_method0("header")?.extract()?;
+        value = YcdValueType::Dict(YcdDict::from([(header, value)]));
+    }
+    serialize(&Dump {
+        value: &value,
+        options,
+    })
+    .map_err(|e| {
+        exceptions::PyValueError::new_err(format!(
+            "Unable to convert the document to {}: {}",
+            format, e
+        ))
+    })
+}
+
+/// Returns the content of the document (without header) as a YAML value, with all
+/// sub-documents converted to their content.
+pub(crate) fn document_value(doc: &Bound<YamlConfigDocument>) -> PyResult<serde_yaml::Value> {
+    let options = DumpOptions {
+        header: false,
+        subdocument_tags: false,
+        sort_keys: false,
+    };
+    dump_document(doc, &options, "YAML", |v| {
+        serde_yaml::to_value(v).map_err(|e| e.to_string())
+    })
+}
+
+pub(crate) fn dump_yaml(
+    doc: &Bound<YamlConfigDocument>,
+    options: &DumpOptions,
+) -> PyResult<String> {
+    dump_document(doc, options, "YAML", |v| {
+        serde_yaml::to_string(v).map_err(|e| e.to_string())
+    })
+}
+
+pub(crate) fn dump_json(
+    doc: &Bound<YamlConfigDocument>,
+    options: &DumpOptions,
+    indent: Option<usize>,
+) -> PyResult<String> {
+    dump_document(doc, options, "JSON", |v| {
+        let Some(indent) = indent else {
+            return serde_json::to_string(v).map_err(|e| e.to_string());
+        };
+        let indent = " ".repeat(indent);
+        let mut out = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+        v.serialize(&mut serializer).map_err(|e| e.to_string())?;
+        String::from_utf8(out).map_err(|e| e.to_string())
+    })
+}
+
+pub(crate) fn dump_toml(
+    doc: &Bound<YamlConfigDocument>,
+    options: &DumpOptions,
+) -> PyResult<String> {
+    dump_document(doc, options, "TOML", |v| {
+        toml::to_string(v).map_err(|e| e.to_string())
+    })
+}
+
+/// Writes the document with all sub-documents inlined and tagged with their class to the YAML
+/// file at path. Raises a ValueError if the document or a sub-document still contains
+/// references.
+pub(crate) fn export_yaml(doc: &Bound<YamlConfigDocument>, path: &str) -> PyResult<()> {
+    let header: String = doc.call_method0("header")?.extract()?;
+    if contains_references(doc.py(), &YcdValueType::Dict(document_content(doc)?))? {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "Unable to export the document {}: It contains references ({}). \
+            Resolve them with resolve_and_merge_references first.",
+            header, REF
+        )));
+    }
+    let content = dump_yaml(
+        doc,
+        &DumpOptions {
+            header: true,
+            subdocument_tags: true,
+            sort_keys: false,
+        },
+    )?;
+    write(path, content)?;
+    Ok(())
+}
+
+fn contains_references(py: Python, value: &YcdValueType) -> PyResult<bool> {
+    match value {
+        YcdValueType::Ycd(doc) => {
+            contains_references(py, &YcdValueType::Dict(document_content(doc.0.bind(py))?))
+        }
+        YcdValueType::Dict(dict) => {
+            if dict.contains_key(REF) {
+                return Ok(true);
+            }
+            for v in dict.values() {
+                if contains_references(py, v)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        YcdValueType::List(list) => {
+            for v in list {
+                if contains_references(py, v)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
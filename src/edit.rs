@@ -0,0 +1,526 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use saphyr_parser::{Event, Parser, ScalarStyle, Span};
+
+use crate::conv::YcdValueType;
+use crate::{DOCUMENT_EXTENSIONS, InvalidDocumentError, YamlConfigDocument, document_headers};
+
+/// Number of spaces to indent keys added below new keys.
+const INDENT: usize = 2;
+
+/// A segment of a path to a value in a document: A key of a dict or an index of a list.
+#[derive(FromPyObject)]
+pub(crate) enum KeySegment {
+    Index(usize),
+    Key(String),
+}
+
+impl Display for KeySegment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySegment::Index(v) => write!(f, "{}", v),
+            KeySegment::Key(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// A node of a YAML document with its location in the source (as char indices).
+enum Node {
+    Scalar {
+        start: usize,
+        end: usize,
+        value: String,
+        style: ScalarStyle,
+        tagged: bool,
+    },
+    Sequence {
+        start: usize,
+        end: usize,
+        flow: bool,
+        tagged: bool,
+        items: Vec<Node>,
+    },
+    Mapping {
+        start: usize,
+        end: usize,
+        flow: bool,
+        tagged: bool,
+        /// Column of the keys.
+        col: usize,
+        entries: Vec<(Node, Node)>,
+    },
+    Alias {
+        start: usize,
+    },
+}
+
+impl Node {
+    fn start(&self) -> usize {
+        match self {
+            Node::Scalar { start, .. }
+            | Node::Sequence { start, .. }
+            | Node::Mapping { start, .. }
+            | Node::Alias { start } => *start,
+        }
+    }
+
+    /// Whether this is an empty scalar (an implicit null, eg. `key:`), which has no text.
+    fn is_empty(&self) -> bool {
+        matches!(self, Node::Scalar { start, end, .. } if start == end)
+    }
+
+    fn is_tagged(&self) -> bool {
+        match self {
+            Node::Scalar { tagged, .. }
+            | Node::Sequence { tagged, .. }
+            | Node::Mapping { tagged, .. } => *tagged,
+            Node::Alias { .. } => false,
+        }
+    }
+
+    /// Index after the last character of the content of this node, excluding trailing
+    /// whitespace and the content after the node (eg. comments).
+    fn content_end(&self, src: &[char]) -> usize {
+        match self {
+            Node::Scalar { end, style, .. } => match style {
+                ScalarStyle::Literal | ScalarStyle::Folded => trim_end(src, *end),
+                _ => *end,
+            },
+            Node::Sequence {
+                end, flow, items, ..
+            } => match (flow, items.last()) {
+                (false, Some(last)) => last.content_end(src),
+                _ => *end,
+            },
+            Node::Mapping {
+                end, flow, entries, ..
+            } => match (flow, entries.last()) {
+                (false, Some((_, last))) => last.content_end(src),
+                _ => *end,
+            },
+            Node::Alias { start } => *start,
+        }
+    }
+
+    /// Index of the first character of this node, including the indicator of block scalars.
+    fn content_start(&self, src: &[char]) -> usize {
+        match self {
+            Node::Scalar {
+                start,
+                style: ScalarStyle::Literal | ScalarStyle::Folded,
+                ..
+            } => {
+                let mut idx = *start;
+                while idx > 0 {
+                    idx -= 1;
+                    if src[idx] == '|' || src[idx] == '>' {
+                        break;
+                    }
+                }
+                idx
+            }
+            _ => self.start(),
+        }
+    }
+
+    fn child(&self, segment: &KeySegment) -> Option<&Node> {
+        match self {
+            Node::Mapping { entries, .. } => {
+                let key = segment.to_string();
+                entries
+                    .iter()
+                    .find(|(k, _)| matches!(k, Node::Scalar { value, .. } if *value == key))
+                    .map(|(_, v)| v)
+            }
+            Node::Sequence { items, .. } => {
+                let idx = match segment {
+                    KeySegment::Index(idx) => *idx,
+                    KeySegment::Key(key) => key.parse().ok()?,
+                };
+                items.get(idx)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the node at path, or the deepest existing node on the way to it and the number of
+    /// segments of path that were found.
+    fn find<'a>(&'a self, path: &[KeySegment]) -> (&'a Node, usize) {
+        let mut node = self;
+        for (i, segment) in path.iter().enumerate() {
+            match node.child(segment) {
+                Some(child) => node = child,
+                None => return (node, i),
+            }
+        }
+        (node, path.len())
+    }
+}
+
+fn trim_end(src: &[char], mut end: usize) -> usize {
+    while end > 0 && src[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    end
+}
+
+/// Parses all documents of the YAML stream in src.
+fn parse_nodes(src: &[char], content: &str) -> Result<Vec<Node>, String> {
+    let mut events = Parser::new_from_str(content);
+    let mut next = move || -> Result<Option<(Event, Span)>, String> {
+        events.next().transpose().map_err(|e| e.to_string())
+    };
+    let mut documents = Vec::new();
+    while let Some((event, span)) = next()? {
+        match event {
+            Event::StreamStart | Event::StreamEnd | Event::DocumentEnd => {}
+            Event::DocumentStart(_) => {
+                let (event, span) = next()?.ok_or("Unexpected end of YAML stream")?;
+                documents.push(parse_node(src, event, span, &mut next)?);
+            }
+            _ => {
+                return Err(format!(
+                    "Unexpected {:?} at line {}",
+                    event,
+                    span.start.line()
+                ));
+            }
+        }
+    }
+    Ok(documents)
+}
+
+fn parse_node<'input>(
+    src: &[char],
+    event: Event<'input>,
+    span: Span,
+    next: &mut impl FnMut() -> Result<Option<(Event<'input>, Span)>, String>,
+) -> Result<Node, String> {
+    let start = span.start.index();
+    let flow = matches!(src.get(start), Some('[' | '{'));
+    match event {
+        Event::Scalar(value, style, _, tag) => Ok(Node::Scalar {
+            start,
+            end: match style {
+                // The spans of quoted scalars may include the rest of the line.
+                ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted => quoted_end(src, start),
+                _ => span.end.index(),
+            },
+            value: value.to_string(),
+            style,
+            tagged: tag.is_some(),
+        }),
+        Event::Alias(_) => Ok(Node::Alias { start }),
+        Event::SequenceStart(_, tag) => {
+            let mut items = Vec::new();
+            loop {
+                let (event, span) = next()?.ok_or("Unexpected end of YAML sequence")?;
+                if let Event::SequenceEnd = event {
+                    return Ok(Node::Sequence {
+                        start,
+                        end: span.end.index(),
+                        flow,
+                        tagged: tag.is_some(),
+                        items,
+                    });
+                }
+                items.push(parse_node(src, event, span, next)?);
+            }
+        }
+        Event::MappingStart(_, tag) => {
+            let mut entries = Vec::new();
+            loop {
+                let (event, span) = next()?.ok_or("Unexpected end of YAML mapping")?;
+                if let Event::MappingEnd = event {
+                    return Ok(Node::Mapping {
+                        start,
+                        end: span.end.index(),
+                        flow,
+                        tagged: tag.is_some(),
+                        col: span_col(src, start),
+                        entries,
+                    });
+                }
+                let key = parse_node(src, event, span, next)?;
+                let (event, span) = next()?.ok_or("Unexpected end of YAML mapping")?;
+                let mut value = parse_node(src, event, span, next)?;
+                if let Node::Scalar { start, end, .. } = &mut value {
+                    // Empty values are located before the colon, move them after it.
+                    if *start == *end {
+                        let colon = src[*start..].iter().position(|c| !c.is_whitespace());
+                        if let Some(offset) = colon.filter(|o| src[*start + o] == ':') {
+                            *start += offset + 1;
+                            *end = *start;
+                        }
+                    }
+                }
+                entries.push((key, value));
+            }
+        }
+        _ => Err(format!(
+            "Unexpected {:?} at line {}",
+            event,
+            span.start.line()
+        )),
+    }
+}
+
+/// Index after the closing quote of the quoted scalar starting at start.
+fn quoted_end(src: &[char], start: usize) -> usize {
+    let quote = src[start];
+    let mut idx = start + 1;
+    while idx < src.len() {
+        match src[idx] {
+            '\\' if quote == '"' => idx += 1,
+            '\'' if quote == '\'' && src.get(idx + 1) == Some(&'\'') => idx += 1,
+            c if c == quote => return idx + 1,
+            _ => {}
+        }
+        idx += 1;
+    }
+    src.len()
+}
+
+/// Column of the char at idx in its line.
+fn span_col(src: &[char], idx: usize) -> usize {
+    src[..idx].iter().rev().take_while(|c| **c != '\n').count()
+}
+
+/// Renders a value to be inserted into a YAML (or JSON) file. Lists and dicts are rendered
+/// in flow style.
+fn render_value(value: &YcdValueType, json: bool) -> Result<String, String> {
+    match value {
+        YcdValueType::YString(v) if !json && !v.contains('\n') => serde_yaml::to_string(v)
+            .map(|v| v.trim_end().to_string())
+            .map_err(|e| e.to_string()),
+        YcdValueType::Bool(_)
+        | YcdValueType::Int(_)
+        | YcdValueType::Float(_)
+        | YcdValueType::Null
+            if !json =>
+        {
+            serde_yaml::to_string(value)
+                .map(|v| v.trim_end().to_string())
+                .map_err(|e| e.to_string())
+        }
+        _ => serde_json::to_string(value).map_err(|e| e.to_string()),
+    }
+}
+
+fn render_key(key: &KeySegment, json: bool) -> Result<String, String> {
+    render_value(&YcdValueType::YString(key.to_string()), json)
+}
+
+/// Sets the value at path in the YAML stream in content. Only the text of the old value is
+/// replaced, or, if the value does not exist yet, a new entry is added to the deepest existing
+/// dict on path. Comments and formatting of the rest of the file are kept.
+///
+/// Of multiple documents in the stream, the last one that contains path is changed, or if none
+/// does, the last one. root_candidates are keys of which one must be at the top of the document,
+/// path is relative to it. If none of them is found, the first one is added.
+fn set_in_content(
+    content: &str,
+    root_candidates: &[String],
+    path: &[KeySegment],
+    value: &YcdValueType,
+    json: bool,
+) -> Result<String, String> {
+    let src: Vec<char> = content.chars().collect();
+    let documents = parse_nodes(&src, content)?;
+    let full_path = |doc: &Node| -> Vec<KeySegment> {
+        let root = root_candidates
+            .iter()
+            .find(|c| doc.child(&KeySegment::Key(c.to_string())).is_some())
+            .or(root_candidates.first());
+        root.map(|r| KeySegment::Key(r.clone()))
+            .into_iter()
+            .chain(path.iter().map(|s| match s {
+                KeySegment::Index(v) => KeySegment::Index(*v),
+                KeySegment::Key(v) => KeySegment::Key(v.clone()),
+            }))
+            .collect()
+    };
+    let document = documents
+        .iter()
+        .rev()
+        .find(|doc| {
+            let path = full_path(doc);
+            doc.find(&path).1 == path.len()
+        })
+        .or(documents.last())
+        .ok_or("The file contains no YAML document")?;
+    let path = full_path(document);
+    let (node, found) = document.find(&path);
+    let (start, end, text) = if found == path.len() {
+        if node.is_tagged() {
+            return Err("The value is tagged and can not be changed".to_string());
+        }
+        if let Node::Alias { .. } = node {
+            return Err("The value is an alias and can not be changed".to_string());
+        }
+        let start = node.content_start(&src);
+        let mut text = render_value(value, json)?;
+        if node.is_empty() {
+            // Separate the new value from the colon or dash before it.
+            text.insert(0, ' ');
+        }
+        (start, node.content_end(&src), text)
+    } else {
+        let Node::Mapping {
+            flow,
+            col,
+            end,
+            entries,
+            tagged: false,
+            ..
+        } = node
+        else {
+            return Err(format!(
+                "Can not add {}, the value at {} is not a dict",
+                path[found],
+                path[..found]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            ));
+        };
+        let missing = &path[found..];
+        if *flow {
+            // Insert before the closing }, missing dicts are added as nested flow dicts.
+            let mut text = render_value(value, json)?;
+            for key in missing[1..].iter().rev() {
+                text = format!("{{{}: {}}}", render_key(key, json)?, text);
+            }
+            let separator = if entries.is_empty() { "" } else { ", " };
+            let text = format!("{}{}: {}", separator, render_key(&missing[0], json)?, text);
+            (end - 1, end - 1, text)
+        } else {
+            // Insert as new lines after the last entry, missing dicts are added as nested blocks.
+            let mut text = String::new();
+            for (i, key) in missing.iter().enumerate() {
+                text.push_str(&" ".repeat(col + i * INDENT));
+                text.push_str(&render_key(key, json)?);
+                text.push(':');
+                if i == missing.len() - 1 {
+                    text.push(' ');
+                    text.push_str(&render_value(value, json)?);
+                }
+                text.push('\n');
+            }
+            let last_end = node.content_end(&src);
+            let pos = match src[last_end..].iter().position(|c| *c == '\n') {
+                Some(offset) => last_end + offset + 1,
+                None => {
+                    text.insert(0, '\n');
+                    src.len()
+                }
+            };
+            (pos, pos, text)
+        }
+    };
+    let mut result: String = src[..start].iter().collect();
+    result.push_str(&text);
+    result.extend(&src[end..]);
+    Ok(result)
+}
+
+/// Sets the value at path in the source files of doc, see YamlConfigDocument.set_in_source.
+pub(crate) fn set_in_source(
+    doc: &Bound<YamlConfigDocument>,
+    path: Vec<KeySegment>,
+    value: YcdValueType,
+    source: Option<String>,
+) -> PyResult<String> {
+    let cls = doc.get_type();
+    let mut root_candidates = document_headers(&cls)?;
+    if cls.call_method0("headerless")?.extract()? {
+        root_candidates.clear();
+    }
+    let source = match source {
+        Some(source) => source,
+        None => find_source(&doc.borrow().absolute_paths, &root_candidates, &path)?,
+    };
+    let json = source.ends_with(".json");
+    if !(json || source.ends_with(".yml") || source.ends_with(".yaml")) {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "Only YAML and JSON files can be edited, {} is neither.",
+            source
+        )));
+    }
+    let content = read_to_string(&source).map_err(|e| {
+        InvalidDocumentError::new_err(format!("Unable to read {}: {:?}", source, e))
+    })?;
+    let changed = set_in_content(&content, &root_candidates, &path, &value, json).map_err(|e| {
+        exceptions::PyValueError::new_err(format!(
+            "Unable to set {} in {}: {}",
+            path.iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+            source,
+            e
+        ))
+    })?;
+    write(&source, changed)?;
+    Ok(source)
+}
+
+/// Returns the first of the files that contains path, or the first file if none does.
+fn find_source(
+    absolute_paths: &[String],
+    root_candidates: &[String],
+    path: &[KeySegment],
+) -> PyResult<String> {
+    let files: Vec<String> = absolute_paths
+        .iter()
+        .flat_map(|p| source_files(p))
+        .collect();
+    for file in &files {
+        let Ok(content) = read_to_string(file) else {
+            continue;
+        };
+        let src: Vec<char> = content.chars().collect();
+        let Ok(documents) = parse_nodes(&src, &content) else {
+            continue;
+        };
+        let contains = |doc: &Node| {
+            let roots: Vec<Option<&Node>> = if root_candidates.is_empty() {
+                vec![Some(doc)]
+            } else {
+                root_candidates
+                    .iter()
+                    .map(|c| doc.child(&KeySegment::Key(c.clone())))
+                    .collect()
+            };
+            roots
+                .into_iter()
+                .flatten()
+                .any(|root| root.find(path).1 == path.len())
+        };
+        if documents.iter().any(contains) {
+            return Ok(file.clone());
+        }
+    }
+    files.into_iter().next().ok_or_else(|| {
+        exceptions::PyValueError::new_err("The document was not loaded from a file.")
+    })
+}
+
+/// The files of an entry of absolute_paths: The file itself, or for referenced documents the
+/// files with one of the DOCUMENT_EXTENSIONS, in the order they override each other.
+fn source_files(path: &str) -> Vec<String> {
+    if Path::new(path).is_file() {
+        return vec![path.to_string()];
+    }
+    DOCUMENT_EXTENSIONS
+        .iter()
+        .rev()
+        .map(|ext| format!("{}.{}", path, ext))
+        .filter(|file| Path::new(file).is_file())
+        .collect()
+}
@@ -2,21 +2,39 @@ use pyo3::prelude::*;
 use pyo3::{PyResult, Python};
 
 use crate::errors::*;
+use crate::lint::*;
 use crate::loader::*;
 use crate::merger::*;
+use crate::registry::*;
+use crate::tags::*;
 use crate::ycd::*;
 
 pub(crate) const REF: &str = "$ref";
 pub(crate) const REMOVE: &str = "$remove";
 pub(crate) const REMOVE_FROM_LIST_PREFIX: &str = "$remove::";
+pub(crate) const FRAGMENT_SEPARATOR: char = '#';
+pub(crate) const WILDCARD: char = '*';
+pub(crate) const IF: &str = "$if";
+pub(crate) const FOR_EACH: &str = "$for_each";
+pub(crate) const FOR_EACH_AS: &str = "$as";
+pub(crate) const FOR_EACH_AS_DEFAULT: &str = "item";
+pub(crate) const FOR_EACH_ITEM: &str = "$item";
+pub(crate) const NAME: &str = "$name";
 pub(crate) const FORCE_STRING: &str = "__forcestring__";
 
+mod compat;
 mod conv;
+mod diff;
+mod dump;
+mod edit;
 pub(crate) mod errors;
+mod lint;
 pub(crate) mod loader;
 pub(crate) mod merger;
 mod minijinja;
 mod pyutil;
+mod registry;
+mod tags;
 pub(crate) mod variables;
 pub(crate) mod ycd;
 
@@ -43,6 +61,13 @@ fn _main(py: Python<'_>, m: Bound<PyModule>) -> PyResult<()> {
     m.add("InvalidRemoveError", py.get_type::<InvalidRemoveError>())?;
 
     m.add_function(wrap_pyfunction!(load_multiple_yml, &m)?)?;
+    m.add_function(wrap_pyfunction!(list_documents, &m)?)?;
+    m.add_function(wrap_pyfunction!(lint_documents, &m)?)?;
+    m.add_function(wrap_pyfunction!(register_document_type, &m)?)?;
+    m.add_function(wrap_pyfunction!(registered_document_types, &m)?)?;
+    m.add_function(wrap_pyfunction!(load_any, &m)?)?;
+    m.add_function(wrap_pyfunction!(load_all, &m)?)?;
+    m.add_function(wrap_pyfunction!(register_tag_handler, &m)?)?;
     m.add_function(wrap_pyfunction!(test_subdoc_specs, &m)?)?;
 
     m.add_class::<YamlConfigDocument>()?;
@@ -0,0 +1,46 @@
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+
+use crate::conv::YcdDict;
+use crate::conv::YcdValueType::YString;
+use crate::{REF, document_files, list_documents};
+
+#[pyfunction]
+/// Lints all documents of the given YamlConfigDocument types in the repositories (lookup paths).
+/// Every document is loaded, its references are resolved, its variables are processed and it is
+/// validated. Errors do not stop the linting of the other documents.
+///
+/// Returns a list of all failures as tuples:
+/// - The path of the document inside the repositories, as it would be used in $ref.
+/// - The files of the document in the repositories.
+/// - The exception raised.
+pub(crate) fn lint_documents(
+    doc_types: Vec<Bound<PyType>>,
+    lookup_paths: Vec<String>,
+) -> PyResult<Vec<(String, Vec<String>, PyErr)>> {
+    let mut failures = Vec::new();
+    for doc_type in doc_types {
+        for (ref_path, _) in list_documents(doc_type.clone(), lookup_paths.clone())? {
+            if let Err(err) = lint_document(&doc_type, &ref_path, &lookup_paths) {
+                let files = document_files(&ref_path, &lookup_paths)?;
+                failures.push((ref_path, files, err));
+            }
+        }
+    }
+    Ok(failures)
+}
+
+/// Loads, resolves, processes and validates a single document, the same way it would be done
+/// when it's referenced.
+fn lint_document(
+    doc_type: &Bound<PyType>,
+    ref_path: &str,
+    lookup_paths: &[String],
+) -> PyResult<()> {
+    let dict = YcdDict::from([(REF.to_string(), YString(ref_path.to_string()))]);
+    let doc = doc_type.call_method1("from_dict", (dict,))?;
+    doc.call_method1("resolve_and_merge_references", (lookup_paths.to_vec(),))?;
+    doc.call_method0("process_vars")?;
+    doc.call_method0("validate")?;
+    Ok(())
+}
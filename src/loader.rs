@@ -1,16 +1,42 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env::current_dir;
-use std::fs::File;
+use std::fmt::Debug;
+use std::fs::{File, read_dir, read_to_string};
+use std::mem::take;
 use std::path::PathBuf;
 
+use glob::Pattern;
+use indexmap::IndexMap;
 use path_absolutize::Absolutize;
 pub(crate) use pyo3::prelude::*;
 use pyo3::types::{PyTuple, PyType};
 use pyo3::{IntoPyObjectExt, exceptions};
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use serde_yaml::{Mapping, Value};
 
-use crate::conv::YcdValueType::YString;
-use crate::conv::{PyYamlConfigDocument, SimpleYcdValueType, YHashMap, YcdDict};
-use crate::{InvalidDocumentError, InvalidHeaderError, REF, YamlConfigDocument, merge_documents};
+use crate::compat::{LoaderOptions, apply_yaml11_booleans, normalize_keys};
+use crate::conv::{PyYamlConfigDocument, SimpleYcdValueType, YIndexMap, YcdDict, YcdValueType};
+use crate::merger::merge_documents_recursion;
+use crate::tags::resolve_tags;
+use crate::{
+    CircularDependencyError, FRAGMENT_SEPARATOR, InvalidDocumentError, InvalidHeaderError, REF,
+    ReferencedDocumentNotFound, WILDCARD, YamlConfigDocument, merge_documents,
+};
+
+/// YAML merge key.
+const MERGE_KEY: &str = "<<";
+
+/// Type name under which raw documents are tracked, that are loaded before the type of the
+/// document they are loaded for is known (to find the discriminator of a subdocument).
+/// These lookups start a chain of their own: Cycles in them are detected there, cycles through
+/// the subdocument once its type is known by the chain of the typed documents.
+const UNKNOWN_TYPE: &str = "?";
+
+/// File extensions of documents in the repositories, in the order they are loaded.
+pub(crate) const DOCUMENT_EXTENSIONS: [&str; 4] = ["yml", "yaml", "toml", "json"];
+/// File extensions of documents, that are not YAML documents.
+const NON_YAML_EXTENSIONS: [&str; 2] = ["toml", "json"];
 
 #[pyfunction]
 #[pyo3(signature = (doc_type, *args))]
@@ -38,6 +64,7 @@ pub(crate) fn load_multiple_yml(
                     doc_type.as_unbound().clone_ref(py),
                     py,
                     arg.clone(),
+                    None,
                 )?;
                 doc = Some(match doc {
                     None => new_doc,
@@ -50,6 +77,80 @@ pub(crate) fn load_multiple_yml(
     Ok(doc.unwrap())
 }
 
+#[pyfunction]
+/// Lists all documents of the given YamlConfigDocument type in the repositories (lookup paths).
+/// Only the headers of the files are checked; the documents are not validated.
+///
+/// Returns a list of tuples, sorted by the first element:
+/// - The first element is the path of the document inside the repositories, as it would be used in $ref.
+/// - The second element is the list of lookup paths that contain this document.
+pub(crate) fn list_documents(
+    doc_type: Bound<PyType>,
+    lookup_paths: Vec<String>,
+) -> PyResult<Vec<(String, Vec<String>)>> {
+    let headers = document_headers(&doc_type)?;
+    let headerless: bool = doc_type.call_method0("headerless")?.extract()?;
+    let mut found: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (lookup_path, repo) in lookup_paths.iter().zip(load_repos(&lookup_paths)) {
+        let mut files = Vec::new();
+        collect_document_files(&PathBuf::from(&repo), &mut files);
+        for file in files {
+            if !read_headers(&file)
+                .is_some_and(|h| headerless || h.iter().any(|h| headers.contains(h)))
+            {
+                continue;
+            }
+            let Ok(in_repo) = file
+                .with_extension("")
+                .strip_prefix(&repo)
+                .map(PathBuf::from)
+            else {
+                continue;
+            };
+            let ref_path = format!("/{}", in_repo.to_string_lossy().replace('\\', "/"));
+            let repos = found.entry(ref_path).or_default();
+            if !repos.contains(lookup_path) {
+                repos.push(lookup_path.clone());
+            }
+        }
+    }
+    Ok(found.into_iter().collect())
+}
+
+/// Recursively collects all files in dir that have one of the DOCUMENT_EXTENSIONS.
+fn collect_document_files(dir: &PathBuf, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_document_files(&path, out);
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| DOCUMENT_EXTENSIONS.contains(&e))
+        {
+            out.push(path);
+        }
+    }
+}
+
+/// Reads only the top-level keys (headers) of a document file (of all documents, if it's a YAML stream). Returns None if the file could not be read.
+fn read_headers(path: &PathBuf) -> Option<Vec<String>> {
+    if extension_of(path.to_str()?).is_some_and(|e| NON_YAML_EXTENSIONS.contains(&e.as_str())) {
+        let document = load_document_file(path.to_str()?, &LoaderOptions::default()).ok()?;
+        return Some(document.into_keys().collect());
+    }
+    let file = File::open(path).ok()?;
+    let mut headers = BTreeSet::new();
+    for document in serde_yaml::Deserializer::from_reader(file) {
+        let document = Option::<HashMap<String, IgnoredAny>>::deserialize(document).ok()?;
+        headers.extend(document.unwrap_or_default().into_keys());
+    }
+    Some(headers.into_iter().collect())
+}
+
 /// Load the full absolute paths to the repositories (lookup paths) stored on disk.
 pub(crate) fn load_repos(lookup_paths: &[String]) -> Vec<String> {
     lookup_paths.iter().map(|p| to_abs_path(p)).collect()
@@ -78,6 +179,7 @@ pub(crate) fn path_in_repo(base_path: &Option<String>, reference_path: &str) ->
         None => reference_path.to_string(),
         // TODO: This isn't truly cross platform but should be OK
         Some(p) => {
+            let (p, _) = split_fragment(p);
             let path: PathBuf = [
                 "/",
                 PathBuf::from(p).parent().unwrap().to_str().unwrap(),
@@ -97,6 +199,44 @@ pub(crate) fn path_in_repo(base_path: &Option<String>, reference_path: &str) ->
     }
 }
 
+/// Splits a $ref-Path into the path of the file and the (optional) fragment after the "#", which selects
+/// a part of the document.
+pub(crate) fn split_fragment(reference: &str) -> (&str, Option<&str>) {
+    match reference.split_once(FRAGMENT_SEPARATOR) {
+        None => (reference, None),
+        Some((path, fragment)) => (path, Some(fragment.trim_matches('/'))),
+    }
+}
+
+/// Returns the key of a document in already_loaded_docs. Documents are tracked by type and path,
+/// since files can contain documents of multiple types.
+pub(crate) fn loaded_document_key(type_name: &str, path: &str) -> String {
+    format!("{}:{}", type_name, path)
+}
+
+/// Returns the references of a $ref entry, which is either a single reference or a list of
+/// references. Raises an InvalidDocumentError for other values. origin is the document that
+/// contains the entry, for error messages.
+pub(crate) fn references_of(value: &YcdValueType, origin: &str) -> PyResult<Vec<String>> {
+    match value {
+        YcdValueType::YString(r) => Ok(vec![r.clone()]),
+        YcdValueType::List(refs) if !refs.is_empty() => refs
+            .iter()
+            .map(|r| match r {
+                YcdValueType::YString(r) => Ok(r.clone()),
+                other => Err(InvalidDocumentError::new_err(format!(
+                    "Invalid entry in the {} list of the document at {}: Expected a reference, got: {}.",
+                    REF, origin, other
+                ))),
+            })
+            .collect(),
+        other => Err(InvalidDocumentError::new_err(format!(
+            "Invalid {} in the document at {}: Expected a reference or a list of references, got: {}.",
+            REF, origin, other
+        ))),
+    }
+}
+
 /// Appends the paths inside repositories to the lookup_paths/repository paths, building a unique
 /// absolute path on the disc that is only missing the file extension.
 ///
@@ -118,29 +258,186 @@ pub(crate) fn absolute_paths(
         .collect::<PyResult<Vec<String>>>()
 }
 
-/// Load the actual dictionaries at path by checking if files ending in .yml/.yaml exist.
-pub(crate) fn load_dicts(path: &str) -> PyResult<Vec<YcdDict>> {
-    let mut doc_dicts: Vec<YcdDict> = Vec::with_capacity(2);
-    if let Some(f) = load_dicts_try_single_path(PathBuf::from(format!("{}.yml", path)))? {
-        doc_dicts.push(f);
+/// Lists the paths (inside the repositories) of all documents that match a $ref-Path with wildcards in
+/// the file name (eg. /services/*), in all repositories. The result is sorted and contains each path once.
+/// Raises ReferencedDocumentNotFound if no document matches and InvalidDocumentError if a directory
+/// of the path contains wildcards.
+///
+/// :param ref_path_in_repo: Path with wildcards absolute to repository root
+/// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
+pub(crate) fn expand_wildcard_path(
+    ref_path_in_repo: &str,
+    lookup_paths: &[String],
+) -> PyResult<Vec<String>> {
+    let (dir, file_pattern) = ref_path_in_repo
+        .rsplit_once('/')
+        .unwrap_or(("", ref_path_in_repo));
+    if dir.contains(WILDCARD) {
+        return Err(InvalidDocumentError::new_err(format!(
+            "Invalid wildcard reference {}: Wildcards are only supported in the file name.",
+            ref_path_in_repo
+        )));
     }
-    if let Some(f) = load_dicts_try_single_path(PathBuf::from(format!("{}.yaml", path)))? {
-        doc_dicts.push(f);
+    let pattern = match Pattern::new(file_pattern) {
+        Ok(p) => p,
+        Err(e) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Invalid wildcard reference {}: {}",
+                ref_path_in_repo, e
+            )));
+        }
+    };
+    let mut out: BTreeSet<String> = BTreeSet::new();
+    for absolute_dir in absolute_paths(dir, lookup_paths)? {
+        let Ok(entries) = read_dir(absolute_dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_path = entry.path();
+            let is_document = file_path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| DOCUMENT_EXTENSIONS.contains(&e));
+            if !is_document || !file_path.is_file() {
+                continue;
+            }
+            if let Some(stem) = file_path.file_stem().and_then(|s| s.to_str()) {
+                if pattern.matches(stem) {
+                    out.insert(format!("{}/{}", dir, stem));
+                }
+            }
+        }
+    }
+    if out.is_empty() {
+        return Err(ReferencedDocumentNotFound::new_err(format!(
+            "No referenced document matches {} in the lookup paths.",
+            ref_path_in_repo
+        )));
+    }
+    Ok(out.into_iter().collect())
+}
+
+/// Lists the files on disk, that make up a document in the repositories.
+///
+/// :param ref_path_in_repo: Path of resource absolute to repository root
+/// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
+pub(crate) fn document_files(
+    ref_path_in_repo: &str,
+    lookup_paths: &[String],
+) -> PyResult<Vec<String>> {
+    let mut out = Vec::new();
+    for absolute_path in absolute_paths(ref_path_in_repo, lookup_paths)? {
+        for ext in DOCUMENT_EXTENSIONS {
+            let file = format!("{}.{}", absolute_path, ext);
+            if PathBuf::from(&file).is_file() {
+                out.push(file);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Load the actual dictionaries at path by checking if files ending in one of the DOCUMENT_EXTENSIONS exist.
+pub(crate) fn load_dicts(path: &str, options: &LoaderOptions) -> PyResult<Vec<YcdDict>> {
+    let mut doc_dicts: Vec<YcdDict> = Vec::with_capacity(DOCUMENT_EXTENSIONS.len());
+    for ext in DOCUMENT_EXTENSIONS {
+        if let Some(f) =
+            load_dicts_try_single_path(PathBuf::from(format!("{}.{}", path, ext)), options)?
+        {
+            doc_dicts.push(f);
+        }
     }
     Ok(doc_dicts)
 }
 
-fn load_dicts_try_single_path(path: PathBuf) -> PyResult<Option<YcdDict>> {
+fn load_dicts_try_single_path(path: PathBuf, options: &LoaderOptions) -> PyResult<Option<YcdDict>> {
     if let Ok(c) = path.absolutize_virtually("/") {
         if c.exists() {
-            return Ok(Some(load_yaml_file(c.to_str().unwrap())?));
+            return Ok(Some(load_document_file(c.to_str().unwrap(), options)?));
         }
     }
     Ok(None)
 }
 
-pub(crate) fn load_yaml_file(path_to_yaml: &str) -> PyResult<YcdDict> {
-    let file = match File::open(path_to_yaml) {
+/// Loads a document file. The format is chosen by the file extension: TOML (.toml), JSON (.json)
+/// or YAML (everything else). If it is a YAML stream of multiple documents (seperated by ---),
+/// the documents are merged in order, like the documents passed to load_multiple_yml.
+pub(crate) fn load_document_file(path: &str, options: &LoaderOptions) -> PyResult<YcdDict> {
+    merge_document_stream(load_document_stream(path, options)?)
+}
+
+/// Merges the documents of a stream in order, later documents override earlier ones.
+pub(crate) fn merge_document_stream(documents: Vec<YcdDict>) -> PyResult<YcdDict> {
+    let mut documents = documents.into_iter();
+    let first = documents.next().unwrap_or_default();
+    Python::attach(|py| {
+        documents
+            .try_fold(YcdValueType::Dict(first), |merged, layer| {
+                merge_documents_recursion(py, merged, YcdValueType::Dict(layer))
+            })
+            .map(YcdValueType::unwrap_dict)
+    })
+}
+
+/// Loads all documents in a document file, in order. Only YAML files can contain multiple documents
+/// (a stream, seperated by ---), other formats always contain one.
+pub(crate) fn load_document_stream(path: &str, options: &LoaderOptions) -> PyResult<Vec<YcdDict>> {
+    match extension_of(path).as_deref() {
+        Some("toml") => Ok(vec![load_non_yaml_file(path, parse_toml)?]),
+        Some("json") => Ok(vec![load_non_yaml_file(path, |content| {
+            serde_json::from_str(content).map_err(|e| e.to_string())
+        })?]),
+        _ => load_yaml_stream(path, options),
+    }
+}
+
+/// Returns the extension of the file at path, in lower case.
+fn extension_of(path: &str) -> Option<String> {
+    Some(PathBuf::from(path).extension()?.to_str()?.to_lowercase())
+}
+
+/// Loads a file, that is not a YAML file, with the given parse function.
+fn load_non_yaml_file(
+    path: &str,
+    parse: impl FnOnce(&str) -> Result<SimpleYcdValueType, String>,
+) -> PyResult<YcdDict> {
+    let content = read_to_string(path).map_err(|e| {
+        InvalidDocumentError::new_err(format!("Unable to open file {}: {:?}", path, e))
+    })?;
+    let parsed = parse(&content).map_err(|e| {
+        InvalidDocumentError::new_err(format!("Unable to read file {}: {}", path, e))
+    })?;
+    match YcdValueType::from(parsed) {
+        YcdValueType::Dict(d) => Ok(d),
+        _ => Err(InvalidDocumentError::new_err(format!(
+            "The document at {} is invalid: Expected a dictionary.",
+            path
+        ))),
+    }
+}
+
+/// Parses a TOML document. Dates and times are converted to strings in ISO 8601 format.
+fn parse_toml(content: &str) -> Result<SimpleYcdValueType, String> {
+    let mut value = toml::Value::Table(toml::from_str(content).map_err(|e| e.to_string())?);
+    toml_datetimes_to_strings(&mut value);
+    value.try_into().map_err(|e: toml::de::Error| e.to_string())
+}
+
+fn toml_datetimes_to_strings(value: &mut toml::Value) {
+    match value {
+        toml::Value::Datetime(datetime) => *value = toml::Value::String(datetime.to_string()),
+        toml::Value::Table(table) => table
+            .iter_mut()
+            .for_each(|(_, v)| toml_datetimes_to_strings(v)),
+        toml::Value::Array(array) => array.iter_mut().for_each(toml_datetimes_to_strings),
+        _ => {}
+    }
+}
+
+/// Loads all documents of a YAML stream (seperated by ---) in a file, in order.
+/// Empty documents in the stream are loaded as empty dicts.
+fn load_yaml_stream(path_to_yaml: &str, options: &LoaderOptions) -> PyResult<Vec<YcdDict>> {
+    let content = match read_to_string(path_to_yaml) {
         Ok(v) => v,
         Err(e) => {
             return Err(InvalidDocumentError::new_err(format!(
@@ -149,14 +446,115 @@ pub(crate) fn load_yaml_file(path_to_yaml: &str) -> PyResult<YcdDict> {
             )));
         }
     };
+    parse_yaml_stream(&content, path_to_yaml, options)
+}
 
-    match serde_yaml::from_reader::<File, HashMap<String, SimpleYcdValueType>>(file) {
-        Ok(v) => Ok(YHashMap(v).into()),
-        Err(e) => Err(InvalidDocumentError::new_err(format!(
-            "Unable to read YAML file {}: {:?}",
-            path_to_yaml, e
-        ))),
+/// Parses all documents of a YAML stream. origin is the path (or name) the content was loaded
+/// from, it is used for error messages and to resolve relative paths in tags.
+pub(crate) fn parse_yaml_stream(
+    content: &str,
+    origin: &str,
+    options: &LoaderOptions,
+) -> PyResult<Vec<YcdDict>> {
+    let mut documents = Vec::with_capacity(1);
+    for document in serde_yaml::Deserializer::from_str(&apply_yaml11_booleans(content, options)) {
+        let read_error = |e: &dyn Debug| {
+            InvalidDocumentError::new_err(format!("Unable to read YAML file {}: {:?}", origin, e))
+        };
+        let mut value = Value::deserialize(document).map_err(|e| read_error(&e))?;
+        resolve_tags(&mut value, origin, options)?;
+        normalize_keys(&mut value, origin, options)?;
+        apply_merge_keys(&mut value).map_err(|e| read_error(&e))?;
+        match serde_yaml::from_value::<Option<IndexMap<String, SimpleYcdValueType>>>(value) {
+            Ok(Some(v)) => documents.push(YIndexMap(v).into()),
+            Ok(None) => documents.push(YcdDict::new()),
+            Err(e) => return Err(read_error(&e)),
+        }
+    }
+    Ok(documents)
+}
+
+/// Applies YAML merge keys (<<) in value, as defined for YAML 1.1 (https://yaml.org/type/merge.html):
+/// The keys of the merged mapping(s) are added to the mapping containing the merge key, unless the mapping
+/// already contains them. If a list of mappings is merged, earlier mappings take precedence.
+/// Like in PyYAML, the merged keys come first, followed by the keys of the mapping.
+/// Merge keys in the merged mappings are applied first.
+fn apply_merge_keys(value: &mut Value) -> Result<(), String> {
+    match value {
+        Value::Mapping(mapping) => {
+            for v in mapping.values_mut() {
+                apply_merge_keys(v)?;
+            }
+            let sources = match mapping.shift_remove(MERGE_KEY) {
+                None => return Ok(()),
+                Some(Value::Mapping(source)) => vec![source],
+                Some(Value::Sequence(sources)) => sources
+                    .into_iter()
+                    .map(|source| match source {
+                        Value::Mapping(source) => Ok(source),
+                        other => Err(format!(
+                            "Expected a mapping to merge with {}, got: {:?}",
+                            MERGE_KEY, other
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                Some(other) => {
+                    return Err(format!(
+                        "Expected a mapping or a list of mappings to merge with {}, got: {:?}",
+                        MERGE_KEY, other
+                    ));
+                }
+            };
+            let mut merged = Mapping::new();
+            for source in sources {
+                for (k, v) in source {
+                    if !mapping.contains_key(&k) {
+                        merged.entry(k).or_insert(v);
+                    }
+                }
+            }
+            merged.extend(take(mapping));
+            *mapping = merged;
+        }
+        Value::Sequence(sequence) => {
+            for v in sequence {
+                apply_merge_keys(v)?;
+            }
+        }
+        Value::Tagged(tagged) => apply_merge_keys(&mut tagged.value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Returns the headers documents of doc_cls can have: The header, followed by the header aliases.
+pub(crate) fn document_headers(doc_cls: &Bound<PyType>) -> PyResult<Vec<String>> {
+    let mut headers: Vec<String> = vec![doc_cls.call_method0("header")?.extract()?];
+    headers.extend(
+        doc_cls
+            .call_method0("header_aliases")?
+            .extract::<Vec<String>>()?,
+    );
+    Ok(headers)
+}
+
+/// Takes the body of a document of type doc_cls out of the loaded file contents.
+/// The body is the value of the header or the first header alias found. If none is found and
+/// doc_cls is headerless, the entire file contents are the body.
+/// Returns None if the file contents don't contain a document of doc_cls.
+pub(crate) fn take_document_body(
+    doc_cls: &Bound<PyType>,
+    mut doc_dict: YcdDict,
+) -> PyResult<Option<YcdValueType>> {
+    for header in document_headers(doc_cls)? {
+        if let Some(body) = doc_dict.shift_remove(&header) {
+            return Ok(Some(body));
+        }
     }
+    if doc_cls.call_method0("headerless")?.extract()? {
+        return Ok(Some(YcdValueType::Dict(doc_dict)));
+    }
+    Ok(None)
 }
 
 /// Converts a loaded dict-object into a specified type of YamlConfigDocument if it's header matches.
@@ -178,9 +576,7 @@ pub(crate) fn dict_to_doc_cls(
     let vrt = buf.absolutize_virtually("/")?;
     let absolute_path: String = vrt.to_str().as_ref().unwrap().to_string();
     let parent_ref = parent.borrow(py);
-    let header = doc_cls.getattr(py, "header")?.call0(py)?;
-    let header: &str = header.extract(py)?;
-    if doc_dict.contains_key(header) {
+    if let Some(body) = take_document_body(doc_cls.bind(py), doc_dict)? {
         let new_abs_paths: Vec<String> = [absolute_path]
             .into_iter()
             .chain(parent_ref.absolute_paths.clone())
@@ -190,7 +586,7 @@ pub(crate) fn dict_to_doc_cls(
             &doc_cls,
             [
                 (&doc_cls).into_py_any(py)?,
-                doc_dict.get(header).unwrap().into_py_any(py)?,
+                body.into_py_any(py)?,
                 ref_path_in_repo.into_py_any(py)?,
                 (&parent).into_py_any(py)?,
                 (&parent_ref.already_loaded_docs).into_py_any(py)?,
@@ -209,29 +605,36 @@ pub(crate) fn dict_to_doc_cls(
 /// Loads a document referenced ($ref) in a YamlConfigDocument
 ///
 /// :param document: The document
+/// :param reference: The reference to load (one entry of the $ref field)
 /// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
 pub(crate) fn load_referenced_document(
     py: Python,
     document: PyYamlConfigDocument,
+    reference: &str,
     lookup_paths: &[String],
 ) -> PyResult<Vec<PyYamlConfigDocument>> {
-    let doc_ref: PyRef<YamlConfigDocument> = document.borrow(py);
-    let ref_path_in_repo;
-    if let YString(path) = doc_ref.doc.get(REF).unwrap() {
-        ref_path_in_repo = path_in_repo(&doc_ref.path, path);
-        if ref_path_in_repo.starts_with("./") || ref_path_in_repo.starts_with("../") {
-            // Invalid path
-            return Ok(vec![]);
-        }
-    } else {
+    let (reference, fragment) = split_fragment(reference);
+    let ref_path_in_repo = path_in_repo(&document.borrow(py).path, reference);
+    if ref_path_in_repo.starts_with("./") || ref_path_in_repo.starts_with("../") {
         // Invalid path
         return Ok(vec![]);
     }
     let doc_cls: Py<PyType> = document.getattr(py, "__class__")?.extract(py)?;
+    if let Some(fragment) = fragment {
+        return load_referenced_fragment(
+            py,
+            document,
+            doc_cls,
+            &ref_path_in_repo,
+            fragment,
+            lookup_paths,
+        );
+    }
+    let options = LoaderOptions::of(doc_cls.bind(py))?;
     // error handling with nested iterators/vectors involved sure is readable.
     let mut out: Vec<PyYamlConfigDocument> = Vec::with_capacity(100);
     for absolute_path in absolute_paths(&ref_path_in_repo, lookup_paths)? {
-        let dicts = load_dicts(&absolute_path)?;
+        let dicts = load_dicts(&absolute_path, &options)?;
         match dicts
             .into_iter()
             .map(|doc_dict| {
@@ -253,6 +656,247 @@ pub(crate) fn load_referenced_document(
     Ok(out)
 }
 
+/// Loads a part of a referenced document ($ref with a fragment, eg. /stacks/full#services/db).
+/// The referenced document is resolved first (regardless of its type), then the part at the
+/// fragment path is converted into the type of the referencing document.
+/// Returns an empty vector if the document or the part of it was not found.
+fn load_referenced_fragment(
+    py: Python,
+    document: PyYamlConfigDocument,
+    doc_cls: Py<PyType>,
+    ref_path_in_repo: &str,
+    fragment: &str,
+    lookup_paths: &[String],
+) -> PyResult<Vec<PyYamlConfigDocument>> {
+    let parent_ref = document.borrow(py);
+    let mut already_loaded_docs = parent_ref.already_loaded_docs.clone().unwrap_or_default();
+    let type_name = doc_cls.bind(py).fully_qualified_name()?.to_string();
+    let options = LoaderOptions::of(doc_cls.bind(py))?;
+    let Some((body, absolute_path)) = load_raw_document(
+        py,
+        &type_name,
+        &options,
+        &[],
+        ref_path_in_repo,
+        lookup_paths,
+        &mut already_loaded_docs,
+    )?
+    else {
+        return Ok(vec![]);
+    };
+    let Some(part) = select_fragment(body, fragment) else {
+        return Ok(vec![]);
+    };
+    let YcdValueType::Dict(part) = part else {
+        return Err(InvalidDocumentError::new_err(format!(
+            "The part {} of the document {} referenced by a document of type {} is not a dictionary.",
+            fragment,
+            ref_path_in_repo,
+            doc_cls.getattr(py, "__name__")?
+        )));
+    };
+    let new_abs_paths: Vec<String> = [absolute_path]
+        .into_iter()
+        .chain(parent_ref.absolute_paths.clone())
+        .collect();
+    Ok(vec![construct_new_ycd(
+        py,
+        &doc_cls,
+        [
+            (&doc_cls).into_py_any(py)?,
+            part.into_py_any(py)?,
+            format!("{}{}{}", ref_path_in_repo, FRAGMENT_SEPARATOR, fragment).into_py_any(py)?,
+            (&document).into_py_any(py)?,
+            (&parent_ref.already_loaded_docs).into_py_any(py)?,
+            new_abs_paths.into_py_any(py)?,
+        ],
+    )?])
+}
+
+/// Loads a referenced document (one entry of a $ref field) without converting it into a
+/// YamlConfigDocument, see load_raw_document. Fragments are supported. Of files with multiple
+/// headers, the document under the one of the given headers is used.
+/// Returns None if the document or the part of it was not found.
+pub(crate) fn load_raw_referenced_document(
+    py: Python,
+    base_path: &Option<String>,
+    reference: &str,
+    headers: &[String],
+    lookup_paths: &[String],
+) -> PyResult<Option<YcdValueType>> {
+    let (reference, fragment) = split_fragment(reference);
+    let ref_path_in_repo = path_in_repo(base_path, reference);
+    // The type of the document is not known, so it is loaded with the default options.
+    let Some((body, _)) = load_raw_document(
+        py,
+        UNKNOWN_TYPE,
+        &LoaderOptions::default(),
+        headers,
+        &ref_path_in_repo,
+        lookup_paths,
+        &mut vec![],
+    )?
+    else {
+        return Ok(None);
+    };
+    Ok(match fragment {
+        None => Some(YcdValueType::Dict(body)),
+        Some(fragment) => select_fragment(body, fragment),
+    })
+}
+
+/// Loads the body of a document from all repositories without converting it into a YamlConfigDocument,
+/// merges them and resolves its $ref entry. The type of the document is not checked; the
+/// file must contain exactly one header, or exactly one of headers (if given).
+/// Returns the body and the absolute path of the file that was loaded last,
+/// or None if the document was not found.
+fn load_raw_document(
+    py: Python,
+    type_name: &str,
+    options: &LoaderOptions,
+    headers: &[String],
+    ref_path_in_repo: &str,
+    lookup_paths: &[String],
+    already_loaded_docs: &mut Vec<String>,
+) -> PyResult<Option<(YcdDict, String)>> {
+    let loaded_doc = loaded_document_key(type_name, ref_path_in_repo);
+    if already_loaded_docs.contains(&loaded_doc) {
+        return Err(CircularDependencyError::new_err(format!(
+            "Infinite circular reference detected while trying to load {}",
+            ref_path_in_repo
+        )));
+    }
+    // Only the documents on the current chain of references are tracked, so that a document can
+    // be referenced by multiple references (eg. in a diamond).
+    already_loaded_docs.push(loaded_doc);
+    let result = load_and_merge_raw_document(
+        py,
+        type_name,
+        options,
+        headers,
+        ref_path_in_repo,
+        lookup_paths,
+        already_loaded_docs,
+    );
+    already_loaded_docs.pop();
+    result
+}
+
+/// Returns the body of a raw document: The value of its only header or, if headers are given and
+/// it has multiple, the value of the one of headers.
+fn raw_document_body(
+    mut doc_dict: YcdDict,
+    headers: &[String],
+    absolute_path: &str,
+) -> PyResult<YcdValueType> {
+    if doc_dict.len() == 1 {
+        return Ok(doc_dict.into_values().next().unwrap());
+    }
+    if headers.is_empty() {
+        return Err(InvalidHeaderError::new_err(format!(
+            "The document {} must contain exactly one header to be referenced by a fragment.",
+            absolute_path
+        )));
+    }
+    let mut found: Vec<&String> = headers
+        .iter()
+        .filter(|h| doc_dict.contains_key(*h))
+        .collect();
+    if found.len() != 1 {
+        return Err(InvalidHeaderError::new_err(format!(
+            "The document {} must contain exactly one of the headers {:?}, found: {:?}.",
+            absolute_path, headers, found
+        )));
+    }
+    let header = found.pop().unwrap();
+    Ok(doc_dict.shift_remove(header).unwrap())
+}
+
+/// Loads, merges and resolves a raw document for load_raw_document.
+fn load_and_merge_raw_document(
+    py: Python,
+    type_name: &str,
+    options: &LoaderOptions,
+    headers: &[String],
+    ref_path_in_repo: &str,
+    lookup_paths: &[String],
+    already_loaded_docs: &mut Vec<String>,
+) -> PyResult<Option<(YcdDict, String)>> {
+    let mut body: Option<YcdValueType> = None;
+    let mut last_path = String::new();
+    for absolute_path in absolute_paths(ref_path_in_repo, lookup_paths)? {
+        for doc_dict in load_dicts(&absolute_path, options)? {
+            let new_body = raw_document_body(doc_dict, headers, &absolute_path)?;
+            body = Some(match body {
+                None => new_body,
+                Some(prev) => merge_documents_recursion(py, prev, new_body)?,
+            });
+            last_path.clone_from(&absolute_path);
+        }
+    }
+    let Some(YcdValueType::Dict(mut body)) = body else {
+        return Ok(None);
+    };
+
+    let references = match body.shift_remove(REF) {
+        Some(refs) => references_of(&refs, &last_path)?,
+        None => vec![],
+    };
+    let mut base: Option<YcdValueType> = None;
+    for reference in references {
+        let (reference_path, fragment) = split_fragment(&reference);
+        let reference_path = path_in_repo(&Some(ref_path_in_repo.to_string()), reference_path);
+        let referenced = load_raw_document(
+            py,
+            type_name,
+            options,
+            headers,
+            &reference_path,
+            lookup_paths,
+            already_loaded_docs,
+        )?
+        .and_then(|(b, _)| match fragment {
+            None => Some(YcdValueType::Dict(b)),
+            Some(fragment) => select_fragment(b, fragment),
+        });
+        let Some(referenced) = referenced else {
+            return Err(ReferencedDocumentNotFound::new_err(format!(
+                "Referenced document {} not found. Requested by a document at {}",
+                reference, last_path
+            )));
+        };
+        base = Some(match base {
+            None => referenced,
+            Some(prev) => merge_documents_recursion(py, prev, referenced)?,
+        });
+    }
+    let body = match base {
+        None => body,
+        Some(base) => merge_documents_recursion(py, base, YcdValueType::Dict(body))?.unwrap_dict(),
+    };
+    Ok(Some((body, last_path)))
+}
+
+/// Selects the part of a document body at the fragment path (keys seperated by "/",
+/// list entries can be selected by their index).
+fn select_fragment(body: YcdDict, fragment: &str) -> Option<YcdValueType> {
+    let mut node = YcdValueType::Dict(body);
+    for key in fragment.split('/').filter(|k| !k.is_empty()) {
+        node = match node {
+            YcdValueType::Dict(mut d) => d.shift_remove(key)?,
+            YcdValueType::List(mut l) => {
+                let idx: usize = key.parse().ok()?;
+                if idx >= l.len() {
+                    return None;
+                }
+                l.swap_remove(idx)
+            }
+            _ => return None,
+        };
+    }
+    Some(node)
+}
+
 #[inline]
 pub(crate) fn construct_new_ycd<'py, T, U>(
     py: Python<'py>,
@@ -1,133 +1,385 @@
-use std::collections::hash_map::Entry;
-use std::iter::Peekable;
+use std::collections::HashMap;
 use std::mem::take;
-use std::str::Split;
 
+use indexmap::map::Entry;
 pub(crate) use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyTuple, PyType};
 use pyo3::{IntoPyObjectExt, exceptions};
 
-use crate::conv::YcdValueType::{Dict, List, YString, Ycd};
+use crate::conv::YcdValueType::{Dict, Int, List, YString, Ycd};
 use crate::conv::{PyYamlConfigDocument, YcdDict, YcdList, YcdValueType};
 use crate::pyutil::ClonePyRef;
+use crate::variables::process_reference_template;
 use crate::{
-    InvalidRemoveError, REF, REMOVE, REMOVE_FROM_LIST_PREFIX, ReferencedDocumentNotFound,
-    YamlConfigDocument, construct_new_ycd, load_referenced_document,
+    InvalidDocumentError, InvalidRemoveError, NAME, REF, REMOVE, REMOVE_FROM_LIST_PREFIX,
+    ReferencedDocumentNotFound, WILDCARD, YamlConfigDocument, construct_new_ycd, document_headers,
+    expand_wildcard_path, load_raw_referenced_document, load_referenced_document, path_in_repo,
+    references_of,
 };
 
-#[derive(FromPyObject)]
-pub(crate) struct SubdocSpec(String, Py<PyType>); // path spec, type
+pub(crate) struct SubdocSpec {
+    path: String,
+    types: SubdocTypes,
+    options: SubdocOptions,
+}
 
-impl SubdocSpec {
-    pub(crate) fn replace_at<C>(&self, from: &mut YcdDict, cb: C, py: Python) -> PyResult<()>
-    where
-        C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
-    {
-        let multiple = self.0.ends_with("[]");
-        let path: Split<char>;
-        let s;
-        if multiple {
-            s = self.0.chars().take(self.0.len() - 2).collect::<String>();
-            path = s.split('/');
+pub(crate) enum SubdocTypes {
+    Single(Py<PyType>),
+    Discriminated(String, HashMap<String, Py<PyType>>), // discriminator key, types by value
+}
+
+/// Options of a subdocument spec, given as an optional dict at the end of the spec tuple.
+pub(crate) struct SubdocOptions {
+    /// Key under which the dict key is inserted into documents in dict collections. None disables it.
+    name_key: Option<String>,
+    /// Key under which the list index is inserted into documents in list collections. None disables it.
+    index_key: Option<String>,
+}
+
+impl Default for SubdocOptions {
+    fn default() -> Self {
+        Self {
+            name_key: Some(NAME.to_string()),
+            index_key: None,
+        }
+    }
+}
+
+impl<'py> FromPyObject<'_, 'py> for SubdocSpec {
+    type Error = PyErr;
+
+    /// Extracts (path, type), (path, discriminator key, types by value), each optionally followed
+    /// by an options dict.
+    fn extract(v: Borrowed<'_, 'py, PyAny>) -> PyResult<Self> {
+        let invalid = || {
+            exceptions::PyValueError::new_err(format!("Invalid subdocument specification: {:?}", v))
+        };
+        let spec = v.cast::<PyTuple>().map_err(|_| invalid())?;
+        if spec.len() < 2 {
+            return Err(invalid());
+        }
+        let path: String = spec.get_item(0)?.extract()?;
+        let (types, options_idx) = match spec.get_item(1)?.extract::<String>() {
+            Ok(key) => {
+                let types = spec.get_item(2).map_err(|_| invalid())?.extract()?;
+                (SubdocTypes::Discriminated(key, types), 3)
+            }
+            Err(_) => (SubdocTypes::Single(spec.get_item(1)?.extract()?), 2),
+        };
+        let options = if spec.len() == options_idx {
+            SubdocOptions::default()
+        } else if spec.len() == options_idx + 1 {
+            SubdocOptions::extract(&spec.get_item(options_idx)?)?
         } else {
-            path = self.0.split('/');
+            return Err(invalid());
+        };
+        Ok(Self {
+            path,
+            types,
+            options,
+        })
+    }
+}
+
+impl SubdocOptions {
+    fn extract(v: &Bound<PyAny>) -> PyResult<Self> {
+        let mut options = Self::default();
+        for (key, value) in v.cast::<PyDict>()?.iter() {
+            match key.extract::<String>()?.as_str() {
+                "name_key" => options.name_key = value.extract()?,
+                "index_key" => options.index_key = value.extract()?,
+                other => {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "Invalid subdocument option: {}",
+                        other
+                    )));
+                }
+            }
         }
-        Self::replace_at_impl(path.peekable(), from, cb, multiple, py)?;
-        Ok(())
+        Ok(options)
     }
-    fn replace_at_impl<'s, C, P>(
-        mut path: Peekable<P>,
-        mut from: &mut YcdDict,
+}
+
+impl SubdocSpec {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns all types the subdocument can have.
+    pub(crate) fn types(&self) -> Vec<&Py<PyType>> {
+        match &self.types {
+            SubdocTypes::Single(typ) => vec![typ],
+            SubdocTypes::Discriminated(_, types) => types.values().collect(),
+        }
+    }
+
+    /// Returns the type of the subdocument that is created from subdoc.
+    /// For discriminated specs the type is chosen by the value of the discriminator key. If subdoc
+    /// doesn't contain it, it is looked up in the documents subdoc references (the last one wins).
+    fn document_type(
+        &self,
+        py: Python,
+        subdoc: &YcdDict,
+        doc_path: &Option<String>,
+        lookup_paths: &[String],
+    ) -> PyResult<Py<PyType>> {
+        let path = &self.path;
+        let (key, types) = match &self.types {
+            SubdocTypes::Single(typ) => return Ok(typ.clone_ref(py)),
+            SubdocTypes::Discriminated(key, types) => (key, types),
+        };
+        let value = match subdoc.get(key) {
+            Some(v) => Some(v.clone_pyref(py)),
+            None => discriminator_from_references(py, subdoc, key, types, doc_path, lookup_paths)?,
+        };
+        match value {
+            Some(YString(value)) => match types.get(&value) {
+                Some(typ) => Ok(typ.clone_ref(py)),
+                None => {
+                    let mut expected: Vec<&String> = types.keys().collect();
+                    expected.sort();
+                    Err(InvalidDocumentError::new_err(format!(
+                        "Unknown value {:?} of the discriminator {:?} for a subdocument at {}. Expected one of: {:?}",
+                        value, key, path, expected
+                    )))
+                }
+            },
+            Some(value) => Err(InvalidDocumentError::new_err(format!(
+                "The discriminator {:?} for a subdocument at {} must be a string, got: {:?}",
+                key, path, value
+            ))),
+            None => Err(InvalidDocumentError::new_err(format!(
+                "A subdocument at {} is missing the discriminator {:?}: {:?}",
+                path, key, subdoc
+            ))),
+        }
+    }
+
+    /// Replaces the value(s) at the path of this spec with the result of cb.
+    /// Path segments ending in "[]" select all values of the dict or list at this key; "*" selects all
+    /// values of the dict at the previous segment (so "a/*/b" is the same as "a[]/b").
+    /// For dict collections ("[]" at the end of the path) expand is called on the dict before the values
+    /// are replaced.
+    pub(crate) fn replace_at<C, E>(
+        &self,
+        from: &mut YcdDict,
         cb: C,
-        multiple: bool,
+        expand: E,
         py: Python,
     ) -> PyResult<()>
     where
         C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
-        P: Iterator<Item = &'s str>,
+        E: Fn(&mut YcdDict) -> PyResult<()>,
     {
-        let mut run_at_least_once = false;
-        while let Some(k) = path.next() {
-            run_at_least_once = true;
-            match path.peek() {
-                None => match from.entry(k.to_string()) {
-                    Entry::Occupied(mut oe) => {
-                        if multiple {
-                            match oe.get_mut() {
-                                Dict(dobj) => {
-                                    *dobj = dobj
-                                        .iter_mut()
-                                        .map(|(k, v)| match cb(v) {
-                                            Ok(nv) => Ok((k.clone(), {
-                                                match nv {
-                                                    Ycd(nvycd) => {
-                                                        // Insert a $name system key to all documents in a dict, which contain the dict key.
-                                                        nvycd.borrow_mut(py).doc.insert(
-                                                            "$name".to_string(),
-                                                            YString(k.to_string()),
-                                                        );
-                                                        Ycd(nvycd)
-                                                    }
-                                                    _ => nv,
-                                                }
-                                            })),
-                                            Err(e) => Err(e),
-                                        })
-                                        .collect::<PyResult<YcdDict>>()?
-                                }
-                                List(lobj) => {
-                                    *lobj =
-                                        lobj.iter_mut().map(&cb).collect::<PyResult<YcdList>>()?
-                                }
-                                YString(s) => {
-                                    if s != REMOVE {
-                                        return Err(exceptions::PyValueError::new_err(format!(
-                                            "Invalid path in subdocument patterns: Invalid reference: {:?}.",
-                                            oe
-                                        )));
-                                    }
-                                }
-                                _ => {
-                                    return Err(exceptions::PyValueError::new_err(format!(
-                                        "Invalid path in subdocument patterns: Invalid reference: {:?}.",
-                                        oe
-                                    )));
-                                }
-                            }
-                        } else {
-                            let w = oe.get_mut();
-                            *w = cb(w)?
-                        }
-                    }
-                    Entry::Vacant(_ve) => return Ok(()),
-                },
-                Some(_) => match from.get_mut(k) {
-                    None => {
+        let segments = self.segments()?;
+        self.replace_at_impl(&segments, from, &cb, &expand, py)
+    }
+
+    /// Returns whether path (the keys and list indices from the root of the document) is the
+    /// position of a subdocument of this spec.
+    pub(crate) fn matches(&self, path: &[String]) -> PyResult<bool> {
+        let mut rest = path;
+        for (key, multiple) in self.segments()? {
+            let len = if multiple { 2 } else { 1 };
+            if rest.len() < len || rest[0] != key {
+                return Ok(false);
+            }
+            rest = &rest[len..];
+        }
+        Ok(rest.is_empty())
+    }
+
+    /// Returns the key under which the dict key (or list index, if in_list) is inserted into the
+    /// subdocuments of this spec, if they are in a collection.
+    pub(crate) fn injected_key(&self, in_list: bool) -> Option<&str> {
+        if !self.path.ends_with("[]") && !self.path.ends_with('*') {
+            return None;
+        }
+        match in_list {
+            true => self.options.index_key.as_deref(),
+            false => self.options.name_key.as_deref(),
+        }
+    }
+
+    /// Returns the type of the subdocument with the (dumped) content value. For discriminated
+    /// specs None is returned if value doesn't contain a known discriminator value.
+    pub(crate) fn value_type(&self, value: &serde_yaml::Value) -> Option<&Py<PyType>> {
+        match &self.types {
+            SubdocTypes::Single(typ) => Some(typ),
+            SubdocTypes::Discriminated(key, types) => types.get(value.get(key.as_str())?.as_str()?),
+        }
+    }
+
+    /// Returns the segments of the path as (key, multiple).
+    fn segments(&self) -> PyResult<Vec<(&str, bool)>> {
+        let mut segments: Vec<(&str, bool)> = Vec::new();
+        for segment in self.path().split('/') {
+            if segment == "*" {
+                match segments.last_mut() {
+                    Some((_, multiple)) if !*multiple => *multiple = true,
+                    _ => {
                         return Err(exceptions::PyValueError::new_err(format!(
-                            "Invalid path in subdocument patterns: Not found (expected a dict at {:?}, got nothing).",
-                            k
+                            "Invalid path in subdocument patterns: Invalid wildcard in {:?}.",
+                            self.path()
                         )));
                     }
-                    Some(v) => match v {
-                        Dict(vv) => from = vv,
-                        _ => {
-                            return Err(exceptions::PyValueError::new_err(format!(
-                                "Invalid path in subdocument patterns: Not found (expected a dict at {:?}, got {:?}).",
-                                k, v
-                            )));
-                        }
-                    },
-                },
+                }
+            } else if let Some(key) = segment.strip_suffix("[]") {
+                segments.push((key, true));
+            } else {
+                segments.push((segment, false));
             }
         }
-        if run_at_least_once {
-            Ok(())
-        } else {
-            Err(exceptions::PyValueError::new_err(
+        Ok(segments)
+    }
+
+    fn replace_at_impl<C, E>(
+        &self,
+        segments: &[(&str, bool)],
+        from: &mut YcdDict,
+        cb: &C,
+        expand: &E,
+        py: Python,
+    ) -> PyResult<()>
+    where
+        C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
+        E: Fn(&mut YcdDict) -> PyResult<()>,
+    {
+        let Some(((k, multiple), rest)) = segments.split_first() else {
+            return Err(exceptions::PyValueError::new_err(
                 "Invalid path in subdocument patterns: Path must not be empty.",
-            ))
+            ));
+        };
+        if rest.is_empty() {
+            return match from.entry(k.to_string()) {
+                Entry::Occupied(mut oe) => {
+                    if *multiple {
+                        self.replace_collection(oe.get_mut(), cb, expand, py)
+                    } else {
+                        let w = oe.get_mut();
+                        *w = cb(w)?;
+                        Ok(())
+                    }
+                }
+                Entry::Vacant(_ve) => Ok(()),
+            };
+        }
+        let Some(v) = from.get_mut(*k) else {
+            // Like the last segment, collections on the way to it are optional.
+            return Ok(());
+        };
+        if !*multiple {
+            return self.descend(k, v, rest, cb, expand, py);
+        }
+        match v {
+            Dict(dobj) => {
+                for item in dobj.values_mut() {
+                    self.descend(k, item, rest, cb, expand, py)?;
+                }
+                Ok(())
+            }
+            List(lobj) => {
+                for item in lobj.iter_mut() {
+                    self.descend(k, item, rest, cb, expand, py)?;
+                }
+                Ok(())
+            }
+            YString(s) if s == REMOVE => Ok(()),
+            _ => Err(exceptions::PyValueError::new_err(format!(
+                "Invalid path in subdocument patterns: Not found (expected a dict or list at {:?}, got {:?}).",
+                k, v
+            ))),
         }
     }
+
+    /// Continues replacing at the rest of the path inside of v, which is a dict or document.
+    fn descend<C, E>(
+        &self,
+        k: &str,
+        v: &mut YcdValueType,
+        rest: &[(&str, bool)],
+        cb: &C,
+        expand: &E,
+        py: Python,
+    ) -> PyResult<()>
+    where
+        C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
+        E: Fn(&mut YcdDict) -> PyResult<()>,
+    {
+        match v {
+            Dict(vv) => self.replace_at_impl(rest, vv, cb, expand, py),
+            Ycd(vv) => self.replace_at_impl(rest, &mut vv.borrow_mut(py).doc, cb, expand, py),
+            YString(s) if s == REMOVE => Ok(()),
+            _ => Err(exceptions::PyValueError::new_err(format!(
+                "Invalid path in subdocument patterns: Not found (expected a dict at {:?}, got {:?}).",
+                k, v
+            ))),
+        }
+    }
+
+    /// Inserts the value into doc under key, if doc is a document and key is set.
+    fn inject<F>(doc: &YcdValueType, key: &Option<String>, value: F, py: Python)
+    where
+        F: FnOnce() -> YcdValueType,
+    {
+        if let (Ycd(ycd), Some(key)) = (doc, key) {
+            ycd.borrow_mut(py).doc.insert(key.clone(), value());
+        }
+    }
+
+    /// Replaces all values of the dict or list collection with the result of cb.
+    fn replace_collection<C, E>(
+        &self,
+        collection: &mut YcdValueType,
+        cb: &C,
+        expand: &E,
+        py: Python,
+    ) -> PyResult<()>
+    where
+        C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
+        E: Fn(&mut YcdDict) -> PyResult<()>,
+    {
+        match collection {
+            Dict(dobj) => {
+                expand(dobj)?;
+                *dobj = dobj
+                    .iter_mut()
+                    .map(|(k, v)| {
+                        let nv = cb(v)?;
+                        // Insert the dict key into all documents in a dict (by default as $name).
+                        Self::inject(&nv, &self.options.name_key, || YString(k.to_string()), py);
+                        Ok((k.clone(), nv))
+                    })
+                    .collect::<PyResult<YcdDict>>()?
+            }
+            List(lobj) => {
+                *lobj = lobj
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, v)| {
+                        let nv = cb(v)?;
+                        Self::inject(&nv, &self.options.index_key, || Int(i as i64), py);
+                        Ok(nv)
+                    })
+                    .collect::<PyResult<YcdList>>()?
+            }
+            YString(s) => {
+                if s != REMOVE {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "Invalid path in subdocument patterns: Invalid reference: {:?}.",
+                        collection
+                    )));
+                }
+            }
+            _ => {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "Invalid path in subdocument patterns: Invalid reference: {:?}.",
+                    collection
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[pyfunction(name = "_test__subdoc_specs")]
@@ -138,9 +390,18 @@ pub(crate) fn test_subdoc_specs(
     mut input: YcdDict,
     replace_with: YcdValueType,
 ) -> PyResult<(YcdDict, Py<PyType>)> {
-    let spec = SubdocSpec(path, typ);
-    spec.replace_at(&mut input, |_| Ok(replace_with.clone_pyref(py)), py)?;
-    Ok((input, spec.1))
+    let spec = SubdocSpec {
+        path,
+        types: SubdocTypes::Single(typ.clone_ref(py)),
+        options: SubdocOptions::default(),
+    };
+    spec.replace_at(
+        &mut input,
+        |_| Ok(replace_with.clone_pyref(py)),
+        |_| Ok(()),
+        py,
+    )?;
+    Ok((input, typ))
 }
 
 /// Removes the $remove:: marker from all lists in doc.
@@ -217,7 +478,7 @@ pub(crate) fn delete_remove_markers(py: Python, doc: YcdValueType) -> PyResult<Y
 //  :param target_node: Node to MERGE INTO
 //  :param source_node: Node to MERGE FROM
 //  :return: Merge result
-fn merge_documents_recursion(
+pub(crate) fn merge_documents_recursion(
     py: Python,
     target_node: YcdValueType,
     source_node: YcdValueType,
@@ -341,74 +602,158 @@ pub(crate) fn merge_documents(
 
 /// Resolve the $ref entry at the beginning of the document body and merge with referenced documents
 /// (changes this document in place).
+/// The $ref entry may also be a list of references. They are resolved independently and merged in order,
+/// before the document is merged on top of them.
 /// May also be extended by subclasses to include sub-document resolving.
 ///
 /// :param doc: Document to work on
 /// :param lookup_paths: Paths to the repositories, where referenced should be looked up.
+/// :param ref_context: If set, templates in $ref entries are rendered with it first.
 pub(crate) fn resolve_and_merge(
     py: Python,
     pydoc: PyYamlConfigDocument,
     lookup_paths: &[String],
+    ref_context: Option<&YcdDict>,
 ) -> PyResult<PyYamlConfigDocument> {
+    if let Some(ctx) = ref_context {
+        process_reference_template(py, &pydoc, ctx)?;
+    }
     let mut pydocrc = pydoc.clone_ref(py);
     let doc: PyRef<YamlConfigDocument> = pydoc.borrow(py);
-    match doc.doc.get(REF) {
-        Some(YString(x)) => {
-            if x == REMOVE {
-                return Ok(pydocrc);
-            }
-        }
-        Some(_) => {}
+    let references: Vec<String> = match doc.doc.get(REF) {
+        Some(YString(x)) if x == REMOVE => return Ok(pydocrc),
+        Some(refs) => references_of(
+            refs,
+            doc.absolute_paths
+                .first()
+                .map(String::as_str)
+                .unwrap_or("<unknown>"),
+        )?,
         None => return Ok(pydocrc),
     };
     drop(doc);
     // Resolve references
     let mut prev_referenced_doc: Option<PyYamlConfigDocument> = None;
-    for mut referenced_doc in load_referenced_document(py, pydocrc.clone_ref(py), lookup_paths)? {
+    for reference in &references {
+        let referenced_doc = resolve_reference(py, &pydocrc, reference, lookup_paths, ref_context)?;
+        prev_referenced_doc = Some(match prev_referenced_doc {
+            None => referenced_doc,
+            // Merge mixins on top of the previous ones
+            Some(pd) => merge_documents(py, referenced_doc, pd)?,
+        });
+    }
+    let prev_referenced_doc = match prev_referenced_doc {
+        Some(pd) => pd,
+        None => {
+            return Err(referenced_document_not_found(
+                py,
+                &pydoc,
+                &pydoc.borrow(py).doc.get(REF).unwrap().to_string(),
+            ));
+        }
+    };
+    // Merge content of current doc into referenced doc (and execute $remove's on the way)
+    pydocrc = merge_documents(py, pydocrc, prev_referenced_doc)?;
+    // Remove $ref entry
+    pydocrc.borrow_mut(py).doc.shift_remove(REF);
+    Ok(pydocrc)
+}
+
+/// Loads a single reference of a document from all repositories, merges the documents found
+/// and resolves their references.
+fn resolve_reference(
+    py: Python,
+    pydoc: &PyYamlConfigDocument,
+    reference: &str,
+    lookup_paths: &[String],
+    ref_context: Option<&YcdDict>,
+) -> PyResult<PyYamlConfigDocument> {
+    let mut prev_referenced_doc: Option<PyYamlConfigDocument> = None;
+    for mut referenced_doc in
+        load_referenced_document(py, pydoc.clone_ref(py), reference, lookup_paths)?
+    {
         if let Some(pd) = prev_referenced_doc {
             // Merge referenced docs
             referenced_doc = merge_documents(py, referenced_doc.clone_ref(py), pd)?;
         }
         prev_referenced_doc = Some(referenced_doc);
     }
-    if prev_referenced_doc.is_none() {
-        let doc: PyRef<YamlConfigDocument> = pydoc.borrow(py);
-        return if doc.absolute_paths.is_empty() {
-            Err(ReferencedDocumentNotFound::new_err(format!(
-                "Referenced document {} not found. Requested by a document at {}",
-                doc.doc.get(REF).unwrap(),
-                doc.absolute_paths[0]
-            )))
-        } else {
-            Err(ReferencedDocumentNotFound::new_err(format!(
-                "Referenced document {} not found.",
-                doc.doc.get(REF).unwrap()
-            )))
-        };
+    match prev_referenced_doc {
+        // Resolve entire referenced docs
+        Some(pd) => resolve_and_merge(py, pd, lookup_paths, ref_context),
+        None => Err(referenced_document_not_found(py, pydoc, reference)),
     }
-    // Resolve entire referenced docs
-    let mut prev_referenced_doc = prev_referenced_doc.unwrap();
-    prev_referenced_doc = resolve_and_merge(py, prev_referenced_doc, lookup_paths)?;
-    // Merge content of current doc into referenced doc (and execute $remove's on the way)
-    pydocrc = merge_documents(py, pydocrc, prev_referenced_doc)?;
-    // Remove $ref entry
-    pydocrc.borrow_mut(py).doc.remove(REF);
-    Ok(pydocrc)
+}
+
+fn referenced_document_not_found(
+    py: Python,
+    pydoc: &PyYamlConfigDocument,
+    reference: &str,
+) -> PyErr {
+    match pydoc.borrow(py).absolute_paths.first() {
+        Some(path) => ReferencedDocumentNotFound::new_err(format!(
+            "Referenced document {} not found. Requested by a document at {}",
+            reference, path
+        )),
+        None => ReferencedDocumentNotFound::new_err(format!(
+            "Referenced document {} not found.",
+            reference
+        )),
+    }
+}
+
+/// Looks up the discriminator key in the documents referenced by subdoc, without converting them
+/// into YamlConfigDocuments. The last reference that contains the key wins.
+/// Of files with multiple headers, the document under the header of one of the types is used.
+fn discriminator_from_references(
+    py: Python,
+    subdoc: &YcdDict,
+    key: &str,
+    types: &HashMap<String, Py<PyType>>,
+    doc_path: &Option<String>,
+    lookup_paths: &[String],
+) -> PyResult<Option<YcdValueType>> {
+    let references = match subdoc.get(REF) {
+        Some(refs) => references_of(refs, doc_path.as_deref().unwrap_or("<unknown>"))?,
+        None => vec![],
+    };
+    let mut headers: Vec<String> = Vec::new();
+    for typ in types.values() {
+        for header in document_headers(typ.bind(py))? {
+            if !headers.contains(&header) {
+                headers.push(header);
+            }
+        }
+    }
+    headers.sort();
+    for reference in references.iter().rev() {
+        if let Some(Dict(mut referenced)) =
+            load_raw_referenced_document(py, doc_path, reference, &headers, lookup_paths)?
+        {
+            if let Some(value) = referenced.shift_remove(key) {
+                return Ok(Some(value));
+            }
+        }
+    }
+    Ok(None)
 }
 
 /// Load a subdocument of a specific type. This will convert the dict at this position
-/// into a YamlConfigDocument with the matching type and perform resolve_and_merge_references
+/// into a YamlConfigDocument with the type matching the spec and perform resolve_and_merge_references
 /// on it.
 pub(crate) fn load_subdocument(
     py: Python,
     doc: &mut YcdValueType,
     args: &[Py<PyAny>; 4],
-    doc_clss: Py<PyType>,
+    spec: &SubdocSpec,
+    doc_path: &Option<String>,
     lookup_paths: &[String],
+    ref_context: Option<&YcdDict>,
 ) -> PyResult<YcdValueType> {
     let ycd = match doc {
         Ycd(v) => v.clone_ref(py),
         Dict(d) => {
+            let doc_clss = spec.document_type(py, d, doc_path, lookup_paths)?;
             let static_args = [(&doc_clss).into_py_any(py)?, (&*d).into_py_any(py)?];
             let args_iter = args.iter().map(|obj| obj.clone_ref(py));
             let new_args = static_args.into_iter().chain(args_iter).collect::<Vec<_>>();
@@ -436,6 +781,7 @@ pub(crate) fn load_subdocument(
         ycd.into(),
         py,
         lookup_paths.to_vec(),
+        ref_context.map(|ctx| ctx.clone_pyref(py)),
     )?
     .into()))
 }
@@ -447,21 +793,68 @@ pub(crate) fn load_subdocuments(
     doc: PyYamlConfigDocument,
     specs: Vec<SubdocSpec>,
     lookup_paths: &[String],
+    ref_context: Option<&YcdDict>,
 ) -> PyResult<()> {
-    let mut doc_borrow = doc.borrow_mut(py);
+    let doc_borrow = doc.borrow(py);
     let args = [
         doc_borrow.path.clone().into_py_any(py)?,
         (&doc).into_py_any(py)?,
         doc_borrow.already_loaded_docs.clone().into_py_any(py)?,
         doc_borrow.absolute_paths.clone().into_py_any(py)?,
     ];
+    let doc_path = doc_borrow.path.clone();
+    // The document must not be borrowed while loading subdocuments, since templated $refs
+    // of subdocuments may access it via parent().
+    let mut new_doc = doc_borrow.doc.clone_pyref(py);
+    drop(doc_borrow);
     for spec in specs {
         spec.replace_at(
-            &mut doc_borrow.doc,
-            |target| load_subdocument(py, target, &args, spec.1.clone_ref(py), lookup_paths),
+            &mut new_doc,
+            |target| {
+                load_subdocument(
+                    py,
+                    target,
+                    &args,
+                    &spec,
+                    &doc_path,
+                    lookup_paths,
+                    ref_context,
+                )
+            },
+            |collection| expand_wildcard_reference(collection, &doc_path, lookup_paths),
             py,
         )?;
     }
+    doc.borrow_mut(py).doc = new_doc;
+    Ok(())
+}
+
+/// Expands a $ref with wildcards (eg. /services/*) in a dict collection of subdocuments into one entry per
+/// matching document, keyed by the file name without extension.
+/// Entries for these documents that are already in the collection get the $ref added, if they don't
+/// have a $ref of their own.
+fn expand_wildcard_reference(
+    collection: &mut YcdDict,
+    doc_path: &Option<String>,
+    lookup_paths: &[String],
+) -> PyResult<()> {
+    let reference = match collection.get(REF) {
+        Some(YString(r)) if r.contains(WILDCARD) => r.clone(),
+        _ => return Ok(()),
+    };
+    collection.shift_remove(REF);
+    let ref_path_in_repo = path_in_repo(doc_path, &reference);
+    for found in expand_wildcard_path(&ref_path_in_repo, lookup_paths)? {
+        let name = found.rsplit('/').next().unwrap().to_string();
+        if let Dict(entry) = collection
+            .entry(name)
+            .or_insert_with(|| Dict(YcdDict::new()))
+        {
+            entry
+                .entry(REF.to_string())
+                .or_insert_with(|| YString(found));
+        }
+    }
     Ok(())
 }
 
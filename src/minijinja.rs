@@ -1,11 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 
-use crate::conv::{PyYamlConfigDocument, SimpleYcdValueType, YHashMap, YcdValueType};
+use crate::conv::{PyYamlConfigDocument, SimpleYcdValueType, YIndexMap, YcdValueType};
 use crate::pyutil::ClonePyRef;
 use crate::{FORCE_STRING, YamlConfigDocument};
-use minijinja::value::{Object, Value, ValueKind};
+use minijinja::value::{Object, Value, ValueKind, merge_maps};
 use minijinja::{Environment, Error, ErrorKind, State};
 use pyo3::IntoPyObjectExt;
 use pyo3::prelude::*;
@@ -26,6 +26,8 @@ pub(crate) struct TemplateRenderer<'env> {
     env: Environment<'env>,
     document: PyYamlConfigDocument,
     globals: HashMap<String, Box<FuncFunc>>,
+    /// Additional variables that are available next to the document's fields.
+    extra_context: Option<Value>,
 }
 
 impl<'env> TemplateRenderer<'env> {
@@ -39,6 +41,7 @@ impl<'env> TemplateRenderer<'env> {
             env: Environment::new(),
             document,
             globals: HashMap::new(),
+            extra_context: None,
         };
 
         slf.env.add_filter(Self::STR_FILTER, str_filter);
@@ -63,11 +66,30 @@ impl<'env> TemplateRenderer<'env> {
         let result = self
             .env
             .get_template(Self::TPL_NAME)?
-            .render(Self::build_context(self.document.clone_ref(py)))?;
+            .render(self.build_context(py))?;
         self.env.remove_template(Self::TPL_NAME);
         Ok(Some(result))
     }
 
+    /// Evaluates a single expression (optionally wrapped in {{ }}) and returns its value.
+    pub(crate) fn eval_expression(
+        self,
+        py: Python<'env>,
+        input: &'env str,
+    ) -> Result<Value, Error> {
+        self.env
+            .compile_expression(strip_expression(input))?
+            .eval(self.build_context(py))
+    }
+
+    /// Returns the (dotted) paths of all variables used in a single expression (optionally wrapped
+    /// in {{ }}), eg. "more.n" for "{{ range(more.n) }}". Functions are included as well.
+    pub(crate) fn expression_variables(input: &str) -> Result<HashSet<String>, Error> {
+        Ok(Environment::new()
+            .compile_expression(strip_expression(input))?
+            .undeclared_variables(true))
+    }
+
     pub(crate) fn add_helpers(&mut self, py: Python, helpers: Vec<Py<PyAny>>) {
         self.globals.extend(helpers.into_iter().map(|f| {
             (
@@ -77,9 +99,19 @@ impl<'env> TemplateRenderer<'env> {
         }));
     }
 
+    /// Makes the values of the given map available to templates. They take precedence over
+    /// fields of the document.
+    pub(crate) fn add_context(&mut self, extra_context: Value) {
+        self.extra_context = Some(extra_context);
+    }
+
     #[inline]
-    fn build_context(document: PyYamlConfigDocument) -> Value {
-        Value::from_object(document)
+    fn build_context(&self, py: Python) -> Value {
+        let document = Value::from_object(self.document.clone_ref(py));
+        match &self.extra_context {
+            None => document,
+            Some(extra) => merge_maps([document, extra.clone()]),
+        }
     }
 
     pub fn create_helper_fn(pyf: Py<PyAny>) -> Box<FuncFunc> {
@@ -114,6 +146,14 @@ impl Display for PyYamlConfigDocument {
     }
 }
 
+/// Removes the {{ }} around an expression, if any.
+fn strip_expression(input: &str) -> &str {
+    let expr = input.trim();
+    expr.strip_prefix("{{")
+        .and_then(|e| e.strip_suffix("}}"))
+        .unwrap_or(expr)
+}
+
 fn str_filter(_state: &State, value: String) -> Result<String, Error> {
     Ok(Value::from(format!("{}{}", FORCE_STRING, value)).to_string())
 }
@@ -150,7 +190,7 @@ impl From<SimpleYcdValueType> for Value {
 impl From<YcdValueType> for Value {
     fn from(in_v: YcdValueType) -> Self {
         match in_v {
-            YcdValueType::Dict(v) => Value::from_object(YHashMap(v)),
+            YcdValueType::Dict(v) => Value::from_object(YIndexMap(v)),
             YcdValueType::List(v) => v
                 .into_iter()
                 .map(|v| v.into())
@@ -171,7 +211,7 @@ impl From<&YcdValueType> for Value {
         match in_v {
             YcdValueType::Dict(v) => {
                 // TODO: Not ideal
-                Python::attach(|py| Value::from_object(YHashMap(v.clone_pyref(py))))
+                Python::attach(|py| Value::from_object(YIndexMap(v.clone_pyref(py))))
             }
             YcdValueType::List(v) => v.iter().map(|v| v.into()).collect::<Vec<Value>>().into(),
             YcdValueType::YString(v) => Value::from(v.clone()),
@@ -278,8 +318,8 @@ impl Object for PyYamlConfigDocument {
     }
 }
 
-struct YHashMapItem<'a>(String, &'a YcdValueType);
-impl Serialize for YHashMapItem<'_> {
+struct YIndexMapItem<'a>(String, &'a YcdValueType);
+impl Serialize for YIndexMapItem<'_> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -288,7 +328,7 @@ impl Serialize for YHashMapItem<'_> {
     }
 }
 
-impl Object for YHashMap<String, YcdValueType> {
+impl Object for YIndexMap<String, YcdValueType> {
     fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
         let name = key.as_str()?;
         self.0.get(name).map(|x| x.into())
@@ -304,7 +344,7 @@ impl Object for YHashMap<String, YcdValueType> {
             "items" => Ok(Value::from(
                 self.0
                     .iter()
-                    .map(|(k, v)| Value::from_serialize(YHashMapItem(k.clone(), v)))
+                    .map(|(k, v)| Value::from_serialize(YIndexMapItem(k.clone(), v)))
                     .collect::<Vec<Value>>(),
             )),
             "values" => Python::attach(|py| {
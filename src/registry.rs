@@ -0,0 +1,120 @@
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{PyDict, PyType};
+
+use crate::compat::LoaderOptions;
+use crate::errors::InvalidHeaderError;
+use crate::{document_headers, load_document_file};
+
+/// Registered YamlConfigDocument types, keyed by their header.
+static REGISTRY: PyOnceLock<Py<PyDict>> = PyOnceLock::new();
+
+fn registry(py: Python<'_>) -> &Bound<'_, PyDict> {
+    REGISTRY
+        .get_or_init(py, || PyDict::new(py).unbind())
+        .bind(py)
+}
+
+#[pyfunction]
+/// Registers a YamlConfigDocument type under its header and header aliases, so that documents of
+/// this type can be loaded by load_any. Can be used as a class decorator. Returns the type.
+///
+/// Registering the same type twice has no effect. Registering a different type with a header
+/// that is already registered raises a ValueError.
+pub(crate) fn register_document_type(doc_type: Bound<PyType>) -> PyResult<Bound<PyType>> {
+    let py = doc_type.py();
+    let headers = document_headers(&doc_type)?;
+    let registry = registry(py);
+    for header in &headers {
+        if let Some(existing) = registry.get_item(header)? {
+            if !existing.is(&doc_type) {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "The header '{}' is already registered for {}.",
+                    header,
+                    existing.str()?
+                )));
+            }
+        }
+    }
+    for header in headers {
+        registry.set_item(header, &doc_type)?;
+    }
+    Ok(doc_type)
+}
+
+#[pyfunction]
+/// Returns a dict of all registered YamlConfigDocument types, keyed by their headers and header aliases.
+pub(crate) fn registered_document_types(py: Python) -> PyResult<Bound<PyDict>> {
+    registry(py).copy()
+}
+
+#[pyfunction]
+/// Loads a YAML document without knowing its type up front. The type is chosen by the header of
+/// the document from the registered types (see register_document_type) and the document is
+/// loaded with its from_yaml.
+///
+/// Raises InvalidHeaderError if none or more than one of the top level keys of the document are
+/// headers of registered types.
+pub(crate) fn load_any(py: Python, path_to_yaml: String) -> PyResult<Bound<PyAny>> {
+    // Only the headers are needed to choose the type, the document is loaded again by its type.
+    let entire_document = load_document_file(&path_to_yaml, &LoaderOptions::default())?;
+    let registry = registry(py);
+    let mut matching = Vec::with_capacity(1);
+    for key in entire_document.keys() {
+        if let Some(doc_type) = registry.get_item(key)? {
+            matching.push((key, doc_type));
+        }
+    }
+    match matching.len() {
+        1 => {
+            let (_, doc_type) = matching.pop().unwrap();
+            doc_type.call_method1("from_yaml", (path_to_yaml,))
+        }
+        0 => Err(InvalidHeaderError::new_err(format!(
+            "The document at {} does not have the header of a registered document type.",
+            path_to_yaml
+        ))),
+        _ => {
+            let mut headers: Vec<&String> = matching.into_iter().map(|(k, _)| k).collect();
+            headers.sort();
+            Err(InvalidHeaderError::new_err(format!(
+                "The document at {} has the headers of multiple registered document types: {:?}. Use load_all to load all of them.",
+                path_to_yaml, headers
+            )))
+        }
+    }
+}
+
+#[pyfunction]
+/// Loads all documents of registered types (see register_document_type) from a YAML file that
+/// contains documents of multiple types, each under its header. Each document is loaded with the
+/// from_yaml of its type. Top level keys that are not headers of registered types are ignored.
+///
+/// Returns a dict of the documents, keyed by the headers of their types.
+pub(crate) fn load_all(py: Python, path_to_yaml: String) -> PyResult<Bound<PyDict>> {
+    // Only the headers are needed to choose the type, the document is loaded again by its type.
+    let entire_document = load_document_file(&path_to_yaml, &LoaderOptions::default())?;
+    let registry = registry(py);
+    let documents = PyDict::new(py);
+    let mut keys: Vec<&String> = entire_document.keys().collect();
+    keys.sort();
+    for key in keys {
+        let Some(doc_type) = registry.get_item(key)? else {
+            continue;
+        };
+        let header = doc_type.call_method0("header")?;
+        if documents.contains(&header)? {
+            return Err(InvalidHeaderError::new_err(format!(
+                "The document at {} contains multiple documents of type {}.",
+                path_to_yaml,
+                doc_type.str()?
+            )));
+        }
+        documents.set_item(
+            header,
+            doc_type.call_method1("from_yaml", (&path_to_yaml,))?,
+        )?;
+    }
+    Ok(documents)
+}
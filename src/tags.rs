@@ -0,0 +1,280 @@
+use std::env;
+use std::fmt::Debug;
+use std::fs::read_to_string;
+use std::mem::replace;
+use std::path::{Path, PathBuf};
+
+use path_absolutize::Absolutize;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{PyDict, PyType};
+use pyo3::{IntoPyObjectExt, exceptions};
+use serde::Deserialize;
+use serde_yaml::Value;
+use serde_yaml::value::TaggedValue;
+
+use crate::compat::{LoaderOptions, apply_yaml11_booleans, normalize_keys};
+use crate::conv::{SimpleYcdValueType, YcdValueType, pyany_to_simple_ycd};
+use crate::merger::SubdocSpec;
+use crate::{InvalidDocumentError, registered_document_types};
+
+const INCLUDE_TAG: &str = "include";
+const FILE_TAG: &str = "file";
+const ENV_TAG: &str = "env";
+
+/// Registered handlers for custom YAML tags, keyed by the tag name (without !).
+static TAG_HANDLERS: PyOnceLock<Py<PyDict>> = PyOnceLock::new();
+
+fn tag_handlers(py: Python<'_>) -> &Bound<'_, PyDict> {
+    TAG_HANDLERS
+        .get_or_init(py, || PyDict::new(py).unbind())
+        .bind(py)
+}
+
+#[pyfunction]
+/// Registers a handler for a custom YAML tag (eg. "!secret"). Whenever a value with this tag
+/// is loaded, it is replaced with the return value of the handler. The handler is called with
+/// the tagged value and the path of the file that contains it.
+///
+/// Handlers for the built-in tags !include, !file and !env replace the built-in behaviour.
+pub(crate) fn register_tag_handler(py: Python, tag: String, handler: Py<PyAny>) -> PyResult<()> {
+    if !handler.bind(py).is_callable() {
+        return Err(exceptions::PyTypeError::new_err(
+            "The tag handler must be callable.",
+        ));
+    }
+    tag_handlers(py).set_item(tag.trim_start_matches('!'), handler)
+}
+
+/// Replaces all tagged values in value, which was loaded from the file at path:
+///
+/// - !include path: The value of the YAML file at path, relative to the directory of the file.
+/// - !file path: The text content of the file at path, relative to the directory of the file.
+/// - !env NAME or !env [NAME, default]: The value of the environment variable NAME.
+/// - Tags registered with register_tag_handler.
+/// - Tags with the name of a YamlConfigDocument class (eg. !Level, as written by export):
+///   The tag is removed, if the documents may contain it (see is_document_tag).
+///
+/// Included files are loaded with options.
+pub(crate) fn resolve_tags(value: &mut Value, path: &str, options: &LoaderOptions) -> PyResult<()> {
+    resolve_tags_impl(value, Path::new(path), options, &mut vec![])
+}
+
+fn resolve_tags_impl(
+    value: &mut Value,
+    path: &Path,
+    options: &LoaderOptions,
+    included: &mut Vec<PathBuf>,
+) -> PyResult<()> {
+    match value {
+        Value::Mapping(mapping) => {
+            for v in mapping.values_mut() {
+                resolve_tags_impl(v, path, options, included)?;
+            }
+        }
+        Value::Sequence(sequence) => {
+            for v in sequence {
+                resolve_tags_impl(v, path, options, included)?;
+            }
+        }
+        Value::Tagged(_) => {
+            if let Value::Tagged(tagged) = replace(value, Value::Null) {
+                *value = resolve_tag(*tagged, path, options, included)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn resolve_tag(
+    tagged: TaggedValue,
+    path: &Path,
+    options: &LoaderOptions,
+    included: &mut Vec<PathBuf>,
+) -> PyResult<Value> {
+    let tag = tagged.tag.to_string();
+    let name = tag.trim_start_matches('!');
+    let handler = Python::attach(|py| {
+        tag_handlers(py)
+            .get_item(name)
+            .map(|h| h.map(|h| h.unbind()))
+    })?;
+    if let Some(handler) = handler {
+        return call_tag_handler(handler, tagged.value, path, options);
+    }
+    let invalid = |expected: &str| {
+        InvalidDocumentError::new_err(format!(
+            "Invalid value for {} in {}: Expected {}.",
+            tag,
+            path.display(),
+            expected
+        ))
+    };
+    match name {
+        INCLUDE_TAG => {
+            let Value::String(target) = tagged.value else {
+                return Err(invalid("a path"));
+            };
+            include_yaml(&relative_to(path, &target)?, options, included)
+        }
+        FILE_TAG => {
+            let Value::String(target) = tagged.value else {
+                return Err(invalid("a path"));
+            };
+            let target = relative_to(path, &target)?;
+            read_to_string(&target).map(Value::String).map_err(|e| {
+                InvalidDocumentError::new_err(format!(
+                    "Unable to read file {} (requested by {} in {}): {:?}",
+                    target.display(),
+                    tag,
+                    path.display(),
+                    e
+                ))
+            })
+        }
+        ENV_TAG => {
+            let (var, default) = match tagged.value {
+                Value::String(var) => (var, None),
+                Value::Sequence(mut args) if args.len() == 2 => {
+                    let default = args.pop();
+                    match args.pop() {
+                        Some(Value::String(var)) => (var, default),
+                        _ => return Err(invalid("a name of an environment variable")),
+                    }
+                }
+                _ => return Err(invalid("a name or a list of a name and a default value")),
+            };
+            match (env::var(&var), default) {
+                (Ok(v), _) => Ok(Value::String(v)),
+                (Err(_), Some(default)) => Ok(default),
+                (Err(_), None) => Err(InvalidDocumentError::new_err(format!(
+                    "The environment variable {} (requested by {} in {}) is not set.",
+                    var,
+                    tag,
+                    path.display()
+                ))),
+            }
+        }
+        _ if Python::attach(|py| is_document_tag(py, options, name))? => {
+            let mut value = tagged.value;
+            resolve_tags_impl(&mut value, path, options, included)?;
+            Ok(value)
+        }
+        _ => Err(InvalidDocumentError::new_err(format!(
+            "Unknown YAML tag {} in {}.",
+            tag,
+            path.display()
+        ))),
+    }
+}
+
+/// Whether name is the name of a document type whose documents may be tagged with it (by export):
+/// The loaded document type, the registered document types or one of their (indirect)
+/// sub-document types.
+fn is_document_tag(py: Python, options: &LoaderOptions, name: &str) -> PyResult<bool> {
+    let mut pending: Vec<Bound<PyType>> = registered_document_types(py)?
+        .values()
+        .into_iter()
+        .map(|t| t.cast_into::<PyType>().map_err(PyErr::from))
+        .collect::<PyResult<_>>()?;
+    pending.extend(options.doc_type.as_ref().map(|t| t.bind(py).clone()));
+    let mut visited: Vec<Bound<PyType>> = Vec::new();
+    while let Some(typ) = pending.pop() {
+        if visited.iter().any(|t| t.is(&typ)) {
+            continue;
+        }
+        if typ.name()? == name {
+            return Ok(true);
+        }
+        for spec in typ
+            .call_method0("subdocuments")?
+            .extract::<Vec<SubdocSpec>>()?
+        {
+            pending.extend(spec.types().into_iter().map(|t| t.bind(py).clone()));
+        }
+        visited.push(typ);
+    }
+    Ok(false)
+}
+
+/// Resolves target relative to the directory of the file at path.
+fn relative_to(path: &Path, target: &str) -> PyResult<PathBuf> {
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(base.join(target).absolutize()?.to_path_buf())
+}
+
+/// Loads the YAML file at target, which must contain a single document, for !include.
+fn include_yaml(
+    target: &Path,
+    options: &LoaderOptions,
+    included: &mut Vec<PathBuf>,
+) -> PyResult<Value> {
+    if included.iter().any(|p| p == target) {
+        return Err(InvalidDocumentError::new_err(format!(
+            "Infinite circular !include detected while trying to include {}",
+            target.display()
+        )));
+    }
+    let content = read_to_string(target).map_err(|e| {
+        InvalidDocumentError::new_err(format!(
+            "Unable to open included YAML file {}: {:?}",
+            target.display(),
+            e
+        ))
+    })?;
+    let read_error = |e: &dyn Debug| {
+        InvalidDocumentError::new_err(format!(
+            "Unable to read included YAML file {}: {:?}",
+            target.display(),
+            e
+        ))
+    };
+    let content = apply_yaml11_booleans(&content, options);
+    let mut documents = serde_yaml::Deserializer::from_str(&content);
+    let mut value = match documents.next() {
+        Some(document) => Value::deserialize(document).map_err(|e| read_error(&e))?,
+        None => Value::Null,
+    };
+    if documents.next().is_some() {
+        return Err(InvalidDocumentError::new_err(format!(
+            "The included YAML file {} contains multiple documents. Only single documents can be included.",
+            target.display()
+        )));
+    }
+    included.push(target.to_path_buf());
+    resolve_tags_impl(&mut value, target, options, included)?;
+    included.pop();
+    normalize_keys(&mut value, &target.to_string_lossy(), options)?;
+    Ok(value)
+}
+
+/// Calls a registered tag handler with the tagged value, after its keys are normalized like the
+/// keys of documents (see normalize_keys).
+fn call_tag_handler(
+    handler: Py<PyAny>,
+    mut value: Value,
+    path: &Path,
+    options: &LoaderOptions,
+) -> PyResult<Value> {
+    normalize_keys(&mut value, &path.to_string_lossy(), options)?;
+    let value: SimpleYcdValueType = serde_yaml::from_value(value).map_err(|e| {
+        InvalidDocumentError::new_err(format!(
+            "Unable to read tagged value in {}: {:?}",
+            path.display(),
+            e
+        ))
+    })?;
+    let result = Python::attach(|py| -> PyResult<SimpleYcdValueType> {
+        let value = YcdValueType::from(value).into_py_any(py)?;
+        let result = handler.call1(py, (value, path.to_string_lossy()))?;
+        pyany_to_simple_ycd(result.into_bound(py))
+    })?;
+    serde_yaml::to_value(result).map_err(|e| {
+        InvalidDocumentError::new_err(format!(
+            "Invalid return value of a tag handler for {}: {:?}",
+            path.display(),
+            e
+        ))
+    })
+}
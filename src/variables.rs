@@ -1,18 +1,26 @@
+use std::mem::take;
+
+use minijinja::value::{Value, merge_maps};
 use pyo3::prelude::*;
 use pyo3::{IntoPyObjectExt, exceptions};
 
-use crate::conv::YcdValueType::{Dict, Int, List, YString, Ycd};
-use crate::conv::{PyYamlConfigDocument, YcdValueType};
+use crate::conv::YcdValueType::{Bool, Dict, Int, List, YString, Ycd};
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdList, YcdValueType};
 use crate::minijinja::TemplateRenderer;
 use crate::pyutil::ClonePyRef;
 use crate::variables::DocumentTraverserCallbackType::{CurrentDoc, SubDoc};
-use crate::{FORCE_STRING, VariableProcessingError};
+use crate::{
+    FOR_EACH, FOR_EACH_AS, FOR_EACH_AS_DEFAULT, FOR_EACH_ITEM, FORCE_STRING, IF, REF,
+    VariableProcessingError,
+};
 
 struct DocumentTraverser;
 
 enum DocumentTraverserCallbackType {
     SubDoc,
-    CurrentDoc(PyYamlConfigDocument),
+    /// The document and whether $for_each entries are expanded even if the values they use
+    /// still contain variables.
+    CurrentDoc(PyYamlConfigDocument, bool),
 }
 
 impl DocumentTraverser {
@@ -24,8 +32,9 @@ impl DocumentTraverser {
         py: Python,
         subdoc: &mut YcdValueType,
         document: PyYamlConfigDocument,
+        force_for_each: bool,
     ) -> PyResult<bool> {
-        Self::traverse(py, &CurrentDoc(document), subdoc)
+        Self::traverse(py, &CurrentDoc(document, force_for_each), subdoc)
     }
 
     fn traverse(
@@ -35,28 +44,218 @@ impl DocumentTraverser {
     ) -> PyResult<bool> {
         match input_node {
             Dict(in_dict) => {
+                if let CurrentDoc(document, _) = callback_type {
+                    check_no_for_each(py, in_dict, document)?;
+                }
                 let mut changed = false;
-                for v in in_dict.values_mut() {
+                // $if entries are evaluated as expressions by apply_conditionals.
+                for (_, v) in in_dict.iter_mut().filter(|(k, _)| *k != IF) {
                     changed |= Self::traverse(py, callback_type, v)?;
                 }
                 Ok(changed)
             }
             List(in_list) => {
-                let mut changed = false;
-                for v in in_list.iter_mut() {
+                let mut changed = match callback_type {
+                    SubDoc => false,
+                    CurrentDoc(base, force) => Self::expand_for_each(py, in_list, base, *force)?,
+                };
+                // Postponed $for_each entries are not processed until they are expanded.
+                for v in in_list.iter_mut().filter(|v| !is_for_each(v)) {
                     changed |= Self::traverse(py, callback_type, v)?;
                 }
                 Ok(changed)
             }
             _ => match callback_type {
                 SubDoc => Self::process_variables_for_subdoc(py, input_node),
-                CurrentDoc(base) => {
+                CurrentDoc(base, _) => {
                     Self::process_variables_current_doc(py, input_node, base.clone_ref(py))
                 }
             },
         }
     }
 
+    /// Expands all $for_each entries in the list. The generated items are inserted in place of
+    /// the $for_each entry. Unless force is set, entries that use values of the document which
+    /// still contain variables are postponed to a later pass. Returns whether the list was changed.
+    fn expand_for_each(
+        py: Python,
+        in_list: &mut YcdList,
+        document: &PyYamlConfigDocument,
+        force: bool,
+    ) -> PyResult<bool> {
+        if !in_list.iter().any(is_for_each) {
+            return Ok(false);
+        }
+        let mut changed = false;
+        let mut new_list = Vec::with_capacity(in_list.len());
+        for item in take(in_list) {
+            match item {
+                Dict(for_each)
+                    if for_each.contains_key(FOR_EACH)
+                        && (force || for_each_inputs_settled(py, &for_each, document)) =>
+                {
+                    new_list.extend(Self::expand_for_each_entry(py, for_each, document, None)?);
+                    changed = true;
+                }
+                _ => new_list.push(item),
+            }
+        }
+        *in_list = new_list;
+        Ok(changed)
+    }
+
+    /// Evaluates the expression of a single $for_each entry and renders its $item once for every
+    /// element, with the element (and the loop state) available to the templates.
+    /// outer_context contains the variables of enclosing $for_each entries.
+    fn expand_for_each_entry(
+        py: Python,
+        mut for_each: YcdDict,
+        document: &PyYamlConfigDocument,
+        outer_context: Option<&Value>,
+    ) -> PyResult<YcdList> {
+        let expr = match for_each.shift_remove(FOR_EACH) {
+            Some(YString(expr)) => expr,
+            v => {
+                return Err(VariableProcessingError::new_err(format!(
+                    "Invalid {} entry, expected an expression string, got: {:?}. Document path: {}.",
+                    FOR_EACH,
+                    v,
+                    document_path(py, document)
+                )));
+            }
+        };
+        let var_name = match for_each.shift_remove(FOR_EACH_AS) {
+            None => FOR_EACH_AS_DEFAULT.to_string(),
+            Some(YString(var_name)) => var_name,
+            Some(v) => {
+                return Err(VariableProcessingError::new_err(format!(
+                    "Invalid {} entry for {} {}, expected a variable name, got: {:?}.",
+                    FOR_EACH_AS, FOR_EACH, expr, v
+                )));
+            }
+        };
+        let template = match for_each.shift_remove(FOR_EACH_ITEM) {
+            Some(template) => template,
+            None => {
+                return Err(VariableProcessingError::new_err(format!(
+                    "{} {} is missing the {} entry.",
+                    FOR_EACH, expr, FOR_EACH_ITEM
+                )));
+            }
+        };
+        if let Some(k) = for_each.keys().next() {
+            return Err(VariableProcessingError::new_err(format!(
+                "Unexpected key {} in {} {}.",
+                k, FOR_EACH, expr
+            )));
+        }
+
+        let mut renderer = TemplateRenderer::new(document.clone_ref(py))?;
+        if let Some(outer) = outer_context {
+            renderer.add_context(outer.clone());
+        }
+        let elements: Vec<Value> = match renderer
+            .eval_expression(py, &expr)
+            .and_then(|v| v.try_iter().map(|it| it.collect()))
+        {
+            Ok(elements) => elements,
+            Err(orig_err) => {
+                return Err(variable_processing_error(
+                    py,
+                    format!(
+                        "Error evaluating the {} expression {}. Document path: {}.",
+                        FOR_EACH,
+                        expr,
+                        document_path(py, document)
+                    ),
+                    exceptions::PyValueError::new_err(format!("{:?}", orig_err)),
+                )?);
+            }
+        };
+
+        let length = elements.len();
+        let mut out = Vec::with_capacity(length);
+        for (index, element) in elements.into_iter().enumerate() {
+            let loop_state = Value::from_iter([
+                ("index0", Value::from(index)),
+                ("index", Value::from(index + 1)),
+                ("first", Value::from(index == 0)),
+                ("last", Value::from(index + 1 == length)),
+            ]);
+            let mut context = Value::from_iter([
+                (var_name.clone(), element),
+                ("loop".to_string(), loop_state),
+            ]);
+            if let Some(outer) = outer_context {
+                context = merge_maps([outer.clone(), context]);
+            }
+            let mut item = template.clone_pyref(py);
+            Self::render_for_each_item(py, &mut item, document, &context)?;
+            out.push(item);
+        }
+        Ok(out)
+    }
+
+    /// Renders all strings in an item generated by $for_each with the loop variables in context.
+    /// Nested $for_each entries are expanded with access to the variables of this one.
+    /// $if entries are evaluated, since the loop variables are not available later.
+    fn render_for_each_item(
+        py: Python,
+        input_node: &mut YcdValueType,
+        document: &PyYamlConfigDocument,
+        context: &Value,
+    ) -> PyResult<()> {
+        match input_node {
+            Dict(in_dict) => {
+                check_no_for_each(py, in_dict, document)?;
+                for (k, v) in in_dict.iter_mut() {
+                    if k == IF {
+                        *v = Bool(evaluate_condition(py, v, document, Some(context))?);
+                    } else {
+                        Self::render_for_each_item(py, v, document, context)?;
+                    }
+                }
+            }
+            List(in_list) => {
+                let mut new_list = Vec::with_capacity(in_list.len());
+                for mut item in take(in_list) {
+                    match item {
+                        Dict(for_each) if for_each.contains_key(FOR_EACH) => new_list.extend(
+                            Self::expand_for_each_entry(py, for_each, document, Some(context))?,
+                        ),
+                        _ => {
+                            Self::render_for_each_item(py, &mut item, document, context)?;
+                            new_list.push(item)
+                        }
+                    }
+                }
+                *in_list = new_list;
+            }
+            YString(in_str) => {
+                let mut renderer = TemplateRenderer::new(document.clone_ref(py))?;
+                renderer.add_context(context.clone());
+                match apply_variable_resolution(py, in_str, renderer) {
+                    Ok(Some(new_value)) => *input_node = new_value,
+                    Ok(None) => {}
+                    Err(orig_err) => {
+                        return Err(variable_processing_error(
+                            py,
+                            format!(
+                                "Error processing a variable in a {} item. Original value was {}. Document path: {}.",
+                                FOR_EACH,
+                                in_str,
+                                document_path(py, document)
+                            ),
+                            orig_err,
+                        )?);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn process_variables_for_subdoc(py: Python, input_node: &mut YcdValueType) -> PyResult<bool> {
         match input_node {
             Ycd(in_ycd) => {
@@ -95,17 +294,15 @@ impl DocumentTraverser {
                             Ok(false)
                         }
                     }
-                    Err(orig_err) => {
-                        let err = VariableProcessingError::new_err(format!(
+                    Err(orig_err) => Err(variable_processing_error(
+                        py,
+                        format!(
                             "Error processing a variable for document. Original value was {}. Document path: {}.",
                             in_str,
-                            document.borrow(py).absolute_paths[0]
-                        ));
-                        let err_obj: Py<PyAny> = (&err).into_py_any(py)?;
-                        let err_pyany: Bound<PyAny> = err_obj.extract(py)?;
-                        err_pyany.setattr("__cause__", orig_err.into_py_any(py)?)?;
-                        Err(err)
-                    }
+                            document_path(py, &document)
+                        ),
+                        orig_err,
+                    )?),
                 }
             }
             _ => Ok(false),
@@ -113,6 +310,117 @@ impl DocumentTraverser {
     }
 }
 
+/// Path of the document for error messages.
+fn document_path(py: Python, document: &PyYamlConfigDocument) -> String {
+    match document.borrow(py).absolute_paths.first() {
+        None => "<unknown>".to_string(),
+        Some(path) => path.clone(),
+    }
+}
+
+fn is_for_each(node: &YcdValueType) -> bool {
+    matches!(node, Dict(d) if d.contains_key(FOR_EACH))
+}
+
+/// Raises a VariableProcessingError if the dict contains a $for_each or $as entry. They are only
+/// allowed in list entries with a $for_each entry, which are expanded instead of being traversed.
+fn check_no_for_each(
+    py: Python,
+    in_dict: &YcdDict,
+    document: &PyYamlConfigDocument,
+) -> PyResult<()> {
+    match [FOR_EACH, FOR_EACH_AS]
+        .into_iter()
+        .find(|k| in_dict.contains_key(*k))
+    {
+        None => Ok(()),
+        Some(key) => Err(VariableProcessingError::new_err(format!(
+            "{} is only allowed in list entries that contain {}. Document path: {}.",
+            key,
+            FOR_EACH,
+            document_path(py, document)
+        ))),
+    }
+}
+
+/// Whether node contains a $for_each entry (outside of sub-documents).
+fn contains_for_each(node: &YcdValueType) -> bool {
+    match node {
+        Dict(d) => d.values().any(contains_for_each),
+        List(l) => l.iter().any(|v| is_for_each(v) || contains_for_each(v)),
+        _ => false,
+    }
+}
+
+/// Whether none of the values of the document that are used by the expression of the
+/// $for_each entry (or of nested $for_each entries) still contain variables.
+/// Variables that are not values of the document (eg. loop variables or functions) are ignored.
+fn for_each_inputs_settled(
+    py: Python,
+    for_each: &YcdDict,
+    document: &PyYamlConfigDocument,
+) -> bool {
+    let mut expressions = vec![];
+    collect_for_each_expressions(for_each, &mut expressions);
+    let doc = document.borrow(py);
+    expressions
+        .iter()
+        .filter_map(|expr| TemplateRenderer::expression_variables(expr).ok())
+        .flatten()
+        .all(|var| {
+            let mut value = Some(&doc.doc);
+            let mut segments = var.split('.').peekable();
+            while let (Some(dict), Some(segment)) = (value, segments.next()) {
+                match dict.get(segment) {
+                    Some(Dict(d)) => value = Some(d),
+                    Some(v) if segments.peek().is_none() => return !contains_variables(v),
+                    _ => value = None,
+                }
+            }
+            value.is_none_or(|d| !d.values().any(contains_variables))
+        })
+}
+
+/// Collects the expressions of the $for_each entry and of all nested $for_each entries.
+fn collect_for_each_expressions<'a>(for_each: &'a YcdDict, out: &mut Vec<&'a str>) {
+    let mut nodes: Vec<&YcdValueType> = for_each.values().collect();
+    if let Some(YString(expr)) = for_each.get(FOR_EACH) {
+        out.push(expr);
+    }
+    while let Some(node) = nodes.pop() {
+        match node {
+            Dict(d) => {
+                if let Some(YString(expr)) = d.get(FOR_EACH) {
+                    out.push(expr);
+                }
+                nodes.extend(d.values());
+            }
+            List(l) => nodes.extend(l),
+            _ => {}
+        }
+    }
+}
+
+/// Whether node contains strings with variables or control structures (outside of
+/// sub-documents, which are already processed).
+fn contains_variables(node: &YcdValueType) -> bool {
+    match node {
+        YString(s) => s.contains("{{") || s.contains("{%"),
+        Dict(d) => d.iter().any(|(k, v)| k != IF && contains_variables(v)),
+        List(l) => l.iter().any(contains_variables),
+        _ => false,
+    }
+}
+
+/// Builds a VariableProcessingError with the original error as it's cause.
+fn variable_processing_error(py: Python, msg: String, orig_err: PyErr) -> PyResult<PyErr> {
+    let err = VariableProcessingError::new_err(msg);
+    let err_obj: Py<PyAny> = (&err).into_py_any(py)?;
+    let err_pyany: Bound<PyAny> = err_obj.extract(py)?;
+    err_pyany.setattr("__cause__", orig_err.into_py_any(py)?)?;
+    Ok(err)
+}
+
 /// Process variables for a document in a single string
 fn apply_variable_resolution<'env>(
     py: Python,
@@ -149,17 +457,184 @@ pub(crate) fn process_variables(py: Python, ycd: PyYamlConfigDocument) -> PyResu
     let mut doc = Dict(ycd.borrow(py).doc.clone_pyref(py));
     DocumentTraverser::run_subdoc_callback(py, &mut doc)?;
     doc = Dict(doc.unwrap_dict());
+    let mut force_for_each = false;
     loop {
-        let changed = DocumentTraverser::run_current_doc_callback(py, &mut doc, ycd.clone_ref(py))?;
-        ycd.borrow_mut(py).doc = doc.unwrap_dict();
+        let changed = DocumentTraverser::run_current_doc_callback(
+            py,
+            &mut doc,
+            ycd.clone_ref(py),
+            force_for_each,
+        )?;
         if !changed {
-            break;
+            // $for_each entries whose values never settle are expanded anyway, so that their
+            // errors are reported.
+            if force_for_each || !contains_for_each(&doc) {
+                ycd.borrow_mut(py).doc = doc.unwrap_dict();
+                break;
+            }
+            force_for_each = true;
         }
+        ycd.borrow_mut(py).doc = doc.unwrap_dict();
         doc = Dict(ycd.borrow_mut(py).doc.clone_pyref(py));
     }
+    let mut doc = Dict(ycd.borrow(py).doc.clone_pyref(py));
+    apply_conditionals(py, &ycd, &mut doc)?;
+    ycd.borrow_mut(py).doc = doc.unwrap_dict();
+    Ok(())
+}
+
+/// Removes all nodes inside of the given node that have a falsy $if entry and removes
+/// the $if entry from all other nodes. The $if entry of the given node itself is not evaluated.
+/// Sub-documents are not traversed, they are expected to already be processed.
+fn apply_conditionals(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    node: &mut YcdValueType,
+) -> PyResult<()> {
+    match node {
+        Dict(in_dict) => {
+            let mut kept = YcdDict::with_capacity(in_dict.len());
+            for (k, mut v) in take(in_dict) {
+                if keep_conditional_node(py, document, &mut v)? {
+                    apply_conditionals(py, document, &mut v)?;
+                    kept.insert(k, v);
+                }
+            }
+            *in_dict = kept;
+        }
+        List(in_list) => {
+            let mut kept = Vec::with_capacity(in_list.len());
+            for mut v in take(in_list) {
+                if keep_conditional_node(py, document, &mut v)? {
+                    apply_conditionals(py, document, &mut v)?;
+                    kept.push(v);
+                }
+            }
+            *in_list = kept;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Evaluates and removes the $if entry of a node, if it has one.
+/// Returns whether the node should be kept. The $if entry of a sub-document is evaluated
+/// in the context of the sub-document.
+fn keep_conditional_node(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    node: &mut YcdValueType,
+) -> PyResult<bool> {
+    let (condition, document) = match node {
+        Dict(in_dict) => (in_dict.shift_remove(IF), document.clone_ref(py)),
+        Ycd(in_ycd) => (
+            in_ycd.borrow_mut(py).doc.shift_remove(IF),
+            in_ycd.clone_ref(py),
+        ),
+        _ => (None, document.clone_ref(py)),
+    };
+    match condition {
+        None => Ok(true),
+        Some(c) => evaluate_condition(py, &c, &document, None),
+    }
+}
+
+/// Evaluates a $if value: Strings are expressions (the {{ }} around them are optional), other
+/// values are used as they are. Returns whether the result is truthy. context contains
+/// additional variables (eg. of $for_each entries).
+fn evaluate_condition(
+    py: Python,
+    condition: &YcdValueType,
+    document: &PyYamlConfigDocument,
+    context: Option<&Value>,
+) -> PyResult<bool> {
+    let YString(expr) = condition else {
+        return Ok(Value::from(condition).is_true());
+    };
+    if expr.trim().is_empty() {
+        return Ok(false);
+    }
+    let mut renderer = TemplateRenderer::new(document.clone_ref(py))?;
+    if let Some(context) = context {
+        renderer.add_context(context.clone());
+    }
+    match renderer.eval_expression(py, expr) {
+        Ok(value) => Ok(value.is_true()),
+        Err(orig_err) => Err(variable_processing_error(
+            py,
+            format!(
+                "Error evaluating the {} expression {}. Document path: {}.",
+                IF,
+                expr,
+                document_path(py, document)
+            ),
+            exceptions::PyValueError::new_err(format!("{:?}", orig_err)),
+        )?),
+    }
+}
+
+/// Renders the templates in the $ref entry of a document (if any) and replaces the entry with the result.
+/// Only the values already present in the document and the given context are available.
+pub(crate) fn process_reference_template(
+    py: Python,
+    ycd: &PyYamlConfigDocument,
+    context: &YcdDict,
+) -> PyResult<()> {
+    let reference = ycd.borrow(py).doc.get(REF).map(|r| r.clone_pyref(py));
+    let rendered = match reference {
+        Some(YString(reference)) => YString(render_reference(py, ycd, context, &reference)?),
+        Some(List(references)) => List(
+            references
+                .iter()
+                .map(|r| match r {
+                    YString(reference) => {
+                        Ok(YString(render_reference(py, ycd, context, reference)?))
+                    }
+                    _ => Ok(r.clone_pyref(py)),
+                })
+                .collect::<PyResult<YcdList>>()?,
+        ),
+        _ => return Ok(()),
+    };
+    ycd.borrow_mut(py).doc.insert(REF.to_string(), rendered);
     Ok(())
 }
 
+fn render_reference(
+    py: Python,
+    ycd: &PyYamlConfigDocument,
+    context: &YcdDict,
+    reference: &str,
+) -> PyResult<String> {
+    let mut renderer = TemplateRenderer::new(ycd.clone_ref(py))?;
+    renderer.add_context(Dict(context.clone_pyref(py)).into());
+    match renderer.render(py, reference) {
+        Ok(Some(rendered)) => {
+            if rendered.contains("{{") || rendered.contains("{%") {
+                return Err(VariableProcessingError::new_err(format!(
+                    "The {} entry {} could not be fully resolved, it resolved to {}. Only values that don't contain variables themselves can be used. Document path: {}.",
+                    REF,
+                    reference,
+                    rendered,
+                    document_path(py, ycd)
+                )));
+            }
+            Ok(rendered)
+        }
+        Ok(None) => Ok(reference.to_string()),
+        Err(orig_err) => Err(variable_processing_error(
+            py,
+            format!(
+                "Error processing the {} entry {}. Document path: {}.",
+                REF,
+                reference,
+                document_path(py, ycd)
+            ),
+            exceptions::PyValueError::new_err(format!("{:?}", orig_err)),
+        )?),
+    }
+}
+
 #[inline]
 pub(crate) fn process_variables_for(
     py: Python,
@@ -4,17 +4,25 @@ use std::mem::take;
 use pyo3::IntoPyObjectExt;
 pub(crate) use pyo3::exceptions;
 pub(crate) use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple, PyType};
 
+use crate::compat::LoaderOptions;
 use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::diff::diff_to_override;
+use crate::dump::{DumpOptions, dump_json, dump_toml, dump_yaml, export_yaml};
+use crate::edit::{KeySegment, set_in_source};
 use crate::pyutil::ClonePyRef;
 use crate::variables::{process_variables, process_variables_for};
 use crate::{
-    CircularDependencyError, InvalidDocumentError, InvalidHeaderError, REF, SchemaError,
-    construct_new_ycd, delete_remove_markers, load_subdocuments, load_yaml_file,
-    recursive_docs_to_dicts, resolve_and_merge,
+    CircularDependencyError, IF, InvalidDocumentError, InvalidHeaderError, REF, SchemaError,
+    construct_new_ycd, delete_remove_markers, load_document_stream, load_subdocuments,
+    loaded_document_key, merge_document_stream, parse_yaml_stream, recursive_docs_to_dicts,
+    resolve_and_merge, take_document_body,
 };
 
+/// Name used in error messages for documents loaded from strings or streams without an origin name.
+const STRING_ORIGIN: &str = "<string>";
+
 /// A document represented by a dictionary, that can be validated,
 /// can contain references to other (sub-)documents, which can be resolved,
 /// and variables that can be parsed.
@@ -43,6 +51,7 @@ impl YamlConfigDocument {
     ///                        If this is not from a repo, leave at None.
     /// :param parent:         Parent document
     #[new]
+    #[classmethod]
     #[pyo3(signature=(
     document,
     path = None,
@@ -51,6 +60,7 @@ impl YamlConfigDocument {
     absolute_paths = None
     ))]
     pub(crate) fn new(
+        cls: &Bound<PyType>,
         document: YcdDict,
         path: Option<String>,
         parent_doc: Option<Py<YamlConfigDocument>>,
@@ -70,7 +80,10 @@ impl YamlConfigDocument {
             already_loaded_docs: None,
         };
 
-        slf.infinite_recursion_check(already_loaded_docs)?;
+        slf.infinite_recursion_check(
+            &cls.fully_qualified_name()?.to_string(),
+            already_loaded_docs,
+        )?;
         Ok(slf)
     }
 
@@ -79,40 +92,78 @@ impl YamlConfigDocument {
     /// Expects the content to be a dictionary with one key (defined in the
     /// header method) and it's value is the body of the document,
     /// validated by the schema method.
+    ///
+    /// Files ending in .toml or .json are loaded as TOML or JSON documents instead.
+    ///
+    /// If the file is a YAML stream of multiple documents, they are merged in order.
+    /// If stream_index is given, only the document at this index is loaded instead.
     #[classmethod]
+    #[pyo3(signature = (path_to_yaml, stream_index = None))]
     pub(crate) fn from_yaml(
         cls: Py<PyType>,
         py: Python,
         path_to_yaml: String,
+        stream_index: Option<usize>,
     ) -> PyResult<PyYamlConfigDocument> {
-        let mut entire_document = load_yaml_file(&path_to_yaml)?;
-        let header = cls.getattr(py, "header")?.call0(py)?;
-        let header: &str = header.extract(py)?;
-        if !entire_document.contains_key(header) {
-            return Err(InvalidHeaderError::new_err(format!(
-                "The document does not have a valid header. Expected was: {}",
-                header
-            )));
-        }
-        let content = entire_document.remove(header).unwrap();
-        match content {
-            YcdValueType::Dict(c) => construct_new_ycd(
-                py,
-                &cls,
-                [
-                    cls.clone_ref(py).into_any(),
-                    c.into_py_any(py)?,
-                    py.None(),
-                    py.None(),
-                    py.None(),
-                    vec![path_to_yaml].into_py_any(py)?,
-                ],
-            ),
-            _ => Err(InvalidDocumentError::new_err(format!(
-                "The document at {} is invalid",
-                path_to_yaml
-            ))),
-        }
+        let options = LoaderOptions::of(cls.bind(py))?;
+        let documents = load_document_stream(&path_to_yaml, &options)?;
+        Self::from_document_stream(cls, py, documents, stream_index, Some(path_to_yaml))
+    }
+
+    /// Constructs a YamlConfigDocument from a string containing YAML, the same way as from_yaml.
+    ///
+    /// origin_name is used in place of a file path: It is stored in absolute_paths, used in error
+    /// messages and relative paths in tags (eg. !include) are resolved relative to it. Without it,
+    /// they are resolved relative to the current working directory.
+    #[classmethod]
+    #[pyo3(signature = (text, origin_name = None, stream_index = None))]
+    pub(crate) fn from_yaml_str(
+        cls: Py<PyType>,
+        py: Python,
+        text: &str,
+        origin_name: Option<String>,
+        stream_index: Option<usize>,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let documents = parse_yaml_stream(
+            text,
+            origin_name.as_deref().unwrap_or(STRING_ORIGIN),
+            &LoaderOptions::of(cls.bind(py))?,
+        )?;
+        Self::from_document_stream(cls, py, documents, stream_index, origin_name)
+    }
+
+    /// Constructs a YamlConfigDocument from a file-like object (eg. sys.stdin) returning
+    /// YAML as str or UTF-8 encoded bytes, the same way as from_yaml_str.
+    ///
+    /// If origin_name is not given, the name attribute of the stream is used, if it has one.
+    #[classmethod]
+    #[pyo3(signature = (stream, origin_name = None, stream_index = None))]
+    pub(crate) fn from_yaml_stream(
+        cls: Py<PyType>,
+        py: Python,
+        stream: Bound<PyAny>,
+        origin_name: Option<String>,
+        stream_index: Option<usize>,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let origin_name = match origin_name {
+            Some(name) => Some(name),
+            None => match stream.getattr_opt("name")? {
+                Some(name) => name.extract::<String>().ok(),
+                None => None,
+            },
+        };
+        let content = stream.call_method0("read")?;
+        let text = match content.cast::<PyBytes>() {
+            Ok(bytes) => String::from_utf8(bytes.as_bytes().to_vec()).map_err(|e| {
+                InvalidDocumentError::new_err(format!(
+                    "Unable to read YAML from {}: {}",
+                    origin_name.as_deref().unwrap_or(STRING_ORIGIN),
+                    e
+                ))
+            })?,
+            Err(_) => content.extract()?,
+        };
+        Self::from_yaml_str(cls, py, &text, origin_name, stream_index)
     }
 
     #[classmethod]
@@ -147,6 +198,34 @@ impl YamlConfigDocument {
         ))
     }
 
+    /// Alternative headers that YAML-documents may contain instead of the header, eg. legacy headers.
+    /// Documents are always returned with the header (see to_dict).
+    #[classmethod]
+    pub(crate) fn header_aliases(_cls: Bound<PyType>) -> Vec<String> {
+        vec![]
+    }
+
+    /// If true, YAML-documents may also contain no header at all. The entire document is then
+    /// treated as the body of the document.
+    #[classmethod]
+    pub(crate) fn headerless(_cls: Bound<PyType>) -> bool {
+        false
+    }
+
+    /// If true, mapping keys that are not strings (eg. 1: or true:) raise an InvalidDocumentError
+    /// when loading YAML-documents. Otherwise they are converted to strings.
+    #[classmethod]
+    pub(crate) fn reject_non_string_keys(_cls: Bound<PyType>) -> bool {
+        false
+    }
+
+    /// If true, the unquoted YAML 1.1 booleans yes, no, on and off (lowercase, capitalized or
+    /// uppercase) are loaded as booleans when loading YAML-documents. Otherwise they are strings, as defined by YAML 1.2.
+    #[classmethod]
+    pub(crate) fn yaml11_booleans(_cls: Bound<PyType>) -> bool {
+        false
+    }
+
     /// Schema that the document should be validated against.
     #[classmethod]
     pub(crate) fn schema(_cls: Bound<PyType>) -> PyResult<Py<PyAny>> {
@@ -165,7 +244,12 @@ impl YamlConfigDocument {
     /// - The first element is the path to the element, with part pieces (nested dicts) seperated by "/".
     ///   If the path ends with [] and at that location is either a list or a dict, then all values will be converted.
     ///   Otherwise only the exact specified path will be converted, it must be a dict, matching the schema.
+    ///   Pieces in the middle of the path can also end with [] to continue in all values of a list or dict.
+    ///   A piece "*" continues in all values of the dict at the previous piece.
     /// - The second element is the referenced document type
+    /// - Optionally a dict with options can follow:
+    ///   - name_key: Key under which the dict key is inserted into documents in dicts (default: "$name", None disables it)
+    ///   - index_key: Key under which the list index is inserted into documents in lists (default: None)
     ///
     /// Example for tuples for a given dict::
     ///
@@ -173,6 +257,7 @@ impl YamlConfigDocument {
     ///     single = ("a/b": ...)
     ///     on_list = ("a/c[]": ...)
     ///     on_dict = ("a/d[]": ...)
+    ///     nested = ("a/c[]/e[]": ...) or ("a/d/*/e[]": ...)
     #[classmethod]
     fn subdocuments(_cls: Bound<PyType>) -> PyResult<Py<PyAny>> {
         debug_assert!(
@@ -204,15 +289,20 @@ impl YamlConfigDocument {
     /// (changes this document in place).
     ///
     /// :param lookup_paths: Paths to the repositories, where referenced should be looked up.
+    /// :param ref_context: If set, templates in $ref entries are rendered before the references
+    ///                     are resolved. Templates can access the values already present in the
+    ///                     document (before merging) and the values of this dict.
     ///
     ///  :final: Since 0.2.0 this function must not be extended. Starting with 1.0.0, subclasses
     ///          overriding this method will be ignored.
     ///
     ///  :returns: self
+    #[pyo3(signature = (lookup_paths, ref_context = None))]
     pub(crate) fn resolve_and_merge_references(
         slf: Py<Self>,
         py: Python,
         lookup_paths: Vec<String>,
+        ref_context: Option<YcdDict>,
     ) -> PyResult<Py<YamlConfigDocument>> {
         if slf.borrow(py).frozen.is_some() {
             return Err(exceptions::PyRuntimeError::new_err(
@@ -231,7 +321,12 @@ impl YamlConfigDocument {
             drop(mref);
         }
 
-        resolve_and_merge(py, slf.clone_ref(py).into(), &lookup_paths)?;
+        resolve_and_merge(
+            py,
+            slf.clone_ref(py).into(),
+            &lookup_paths,
+            ref_context.as_ref(),
+        )?;
 
         if let Ok(cb) = slf.getattr(py, "_initialize_data_after_merge") {
             let mut mref = slf.borrow_mut(py);
@@ -244,7 +339,13 @@ impl YamlConfigDocument {
         }
 
         let subdoc_spec = slf.call_method0(py, "subdocuments")?.extract(py)?;
-        load_subdocuments(py, slf.clone_ref(py).into(), subdoc_spec, &lookup_paths)?;
+        load_subdocuments(
+            py,
+            slf.clone_ref(py).into(),
+            subdoc_spec,
+            &lookup_paths,
+            ref_context.as_ref(),
+        )?;
 
         let mut self_: PyRefMut<Self> = slf.borrow_mut(py);
         let d = take(&mut self_.doc);
@@ -269,6 +370,8 @@ impl YamlConfigDocument {
             ));
         }
         process_variables(py, slf.clone_ref(py).into())?;
+        // A $if at the root of the document can not remove anything, since the document has no parent.
+        slf.borrow_mut(py).doc.shift_remove(IF);
         if let Ok(cb) = slf.getattr(py, "_initialize_data_after_variables") {
             let mut mref = slf.borrow_mut(py);
             let args = PyTuple::new(py, take(&mut mref.doc))?;
@@ -418,7 +521,7 @@ impl YamlConfigDocument {
         match frozen {
             None => {
                 let self_: PyRef<Self> = slf.borrow(py);
-                let mut dict: YcdDict = HashMap::new();
+                let mut dict = YcdDict::new();
                 dict.insert(
                     slf.getattr(py, "header")?.call0(py)?.extract(py)?,
                     YcdValueType::Dict(self_.doc.clone_pyref(py)),
@@ -437,6 +540,110 @@ impl YamlConfigDocument {
         }
     }
 
+    /// Returns the document as YAML. If the document is frozen, its frozen doc is used.
+    ///
+    /// - header: Whether the document is returned under its header, like to_dict.
+    /// - subdocument_tags: Whether sub-documents are tagged with the name of their class (eg. !Level).
+    /// - sort_keys: Whether keys are sorted. Otherwise they are in the order of the document.
+    #[pyo3(signature = (header = true, subdocument_tags = false, sort_keys = false))]
+    fn to_yaml(
+        slf: &Bound<Self>,
+        header: bool,
+        subdocument_tags: bool,
+        sort_keys: bool,
+    ) -> PyResult<String> {
+        dump_yaml(
+            slf,
+            &DumpOptions {
+                header,
+                subdocument_tags,
+                sort_keys,
+            },
+        )
+    }
+
+    /// Returns the document as JSON, see to_yaml. If indent is given, the JSON is pretty-printed
+    /// with this number of spaces.
+    #[pyo3(signature = (header = true, sort_keys = false, indent = None))]
+    fn to_json(
+        slf: &Bound<Self>,
+        header: bool,
+        sort_keys: bool,
+        indent: Option<usize>,
+    ) -> PyResult<String> {
+        dump_json(
+            slf,
+            &DumpOptions {
+                header,
+                subdocument_tags: false,
+                sort_keys,
+            },
+            indent,
+        )
+    }
+
+    /// Returns the document as TOML, see to_yaml. Raises a ValueError if the document
+    /// contains values that can not be represented in TOML, eg. None.
+    #[pyo3(signature = (header = true, sort_keys = false))]
+    fn to_toml(slf: &Bound<Self>, header: bool, sort_keys: bool) -> PyResult<String> {
+        dump_toml(
+            slf,
+            &DumpOptions {
+                header,
+                subdocument_tags: false,
+                sort_keys,
+            },
+        )
+    }
+
+    /// Writes the document as a self-contained YAML file to path, that can be loaded with from_yaml
+    /// without lookup paths. All references must be resolved beforehand
+    /// (resolve_and_merge_references). Sub-documents are inlined and tagged with the name of their
+    /// class (eg. !Level); from_yaml ignores these tags.
+    ///
+    /// If this is called before process_vars, the {{ variables }} are written unprocessed,
+    /// otherwise their results are written.
+    fn export(slf: &Bound<Self>, path: &str) -> PyResult<()> {
+        export_yaml(slf, path)
+    }
+
+    /// Returns the smallest document (as YAML) that references the document at reference and
+    /// results in the current content of this document, when its references are resolved with
+    /// lookup_paths. It only contains the changed keys, $remove for removed keys and $remove::
+    /// entries for strings removed from lists.
+    ///
+    /// This document should have its references resolved, but its variables not processed.
+    /// Raises a ValueError if a list can not be the result of merging: Merging only appends to
+    /// lists and only strings can be removed from them.
+    fn diff_to_override(
+        slf: &Bound<Self>,
+        reference: &str,
+        lookup_paths: Vec<String>,
+    ) -> PyResult<String> {
+        diff_to_override(slf, reference, lookup_paths)
+    }
+
+    /// Sets the value at key_path (a list of keys of dicts and indices of lists, relative to
+    /// the document body) in the YAML file that defines it, keeping comments and formatting of
+    /// the file. Only the text of the old value is replaced. If the value does not exist yet, it
+    /// is added to the deepest dict on key_path that exists, as a new entry after its last entry.
+    ///
+    /// The file is the first of absolute_paths that contains the value (the file its value is
+    /// loaded from), or the first one if none does. A different file can be passed as source.
+    /// Lists and dicts are written in flow style.
+    ///
+    /// The document itself is not changed; load it again to see the change.
+    /// Returns the path of the changed file.
+    #[pyo3(signature = (key_path, value, source = None))]
+    fn set_in_source(
+        slf: &Bound<Self>,
+        key_path: Vec<KeySegment>,
+        value: YcdValueType,
+        source: Option<String>,
+    ) -> PyResult<String> {
+        set_in_source(slf, key_path, value, source)
+    }
+
     /// If not frozen: Returns a COPY of the key at the specified location
     /// Otherwise returns it from the frozen `self.doc`, it may or may not be a copy.
     fn internal_get(slf: &Bound<Self>, key: &str) -> PyResult<Py<PyAny>> {
@@ -485,7 +692,7 @@ impl YamlConfigDocument {
             }
         };
         // None:
-        slf.borrow_mut().doc.remove(key);
+        slf.borrow_mut().doc.shift_remove(key);
         Ok(())
     }
 
@@ -497,16 +704,70 @@ impl YamlConfigDocument {
 }
 
 impl YamlConfigDocument {
-    /// Infinite recursion check
-    fn infinite_recursion_check(&mut self, mut already_loaded_docs: Vec<String>) -> PyResult<()> {
+    /// Constructs a document of type cls from the documents of a stream loaded from origin.
+    /// The documents are merged in order, or only the one at stream_index is used.
+    fn from_document_stream(
+        cls: Py<PyType>,
+        py: Python,
+        documents: Vec<YcdDict>,
+        stream_index: Option<usize>,
+        origin: Option<String>,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let origin_str = origin.as_deref().unwrap_or(STRING_ORIGIN).to_string();
+        let entire_document = match stream_index {
+            None => merge_document_stream(documents)?,
+            Some(idx) => match documents.into_iter().nth(idx) {
+                Some(d) => d,
+                None => {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "The YAML file {} has no document at index {}.",
+                        origin_str, idx
+                    )));
+                }
+            },
+        };
+        let Some(content) = take_document_body(cls.bind(py), entire_document)? else {
+            let header = cls.getattr(py, "header")?.call0(py)?;
+            return Err(InvalidHeaderError::new_err(format!(
+                "The document does not have a valid header. Expected was: {}",
+                header
+            )));
+        };
+        match content {
+            YcdValueType::Dict(c) => construct_new_ycd(
+                py,
+                &cls,
+                [
+                    cls.clone_ref(py).into_any(),
+                    c.into_py_any(py)?,
+                    py.None(),
+                    py.None(),
+                    py.None(),
+                    origin.into_iter().collect::<Vec<_>>().into_py_any(py)?,
+                ],
+            ),
+            _ => Err(InvalidDocumentError::new_err(format!(
+                "The document at {} is invalid",
+                origin_str
+            ))),
+        }
+    }
+
+    /// Infinite recursion check (see loaded_document_key)
+    fn infinite_recursion_check(
+        &mut self,
+        type_name: &str,
+        mut already_loaded_docs: Vec<String>,
+    ) -> PyResult<()> {
         if let Some(path) = &self.path {
-            if already_loaded_docs.contains(path) {
+            let loaded_doc = loaded_document_key(type_name, path);
+            if already_loaded_docs.contains(&loaded_doc) {
                 return Err(CircularDependencyError::new_err(format!(
                     "Infinite circular reference detected while trying to load {}",
                     path
                 )));
             }
-            already_loaded_docs.push(path.clone());
+            already_loaded_docs.push(loaded_doc);
         }
         self.already_loaded_docs = Some(already_loaded_docs);
         Ok(())
@@ -0,0 +1,92 @@
+import os
+from tempfile import TemporaryDirectory
+
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Commands
+
+
+class DiffOverride(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'diff_override'
+
+    def load_customized(self):
+        return self.load_base('customized.yml', [])
+
+    def load_override(self, content):
+        with TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, 'override.yml')
+            with open(path, 'w') as f:
+                f.write(content)
+            return Base.from_yaml(path).resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_diff_to_override(self):
+        doc = self.load_customized()
+        self.assertEqual(
+            'base:\n'
+            '  $ref: /base\n'
+            '  str_field: customized\n'
+            '  level_dict:\n'
+            '    changed:\n'
+            '      name: renamed\n'
+            '    added:\n'
+            '      name: added\n'
+            '  more:\n'
+            '    nested:\n'
+            '      b: 3\n'
+            '    list:\n'
+            '    - $remove::y\n'
+            '    - w\n'
+            '    new: value\n'
+            '    removed: $remove\n',
+            doc.diff_to_override('/base', [self.fix_get_path('repo')])
+        )
+
+    def test_diff_to_override_round_trip(self):
+        doc = self.load_customized()
+        override = self.load_override(doc.diff_to_override('/base', [self.fix_get_path('repo')]))
+        self.assertEqual(doc.to_dict(), override.to_dict())
+
+    def test_diff_to_override_unchanged(self):
+        doc = self.load_base('unchanged.yml', ['repo'])
+        self.assertEqual(
+            'base:\n'
+            '  $ref: /base\n',
+            doc.diff_to_override('/base', [self.fix_get_path('repo')])
+        )
+
+    def test_diff_to_override_frozen(self):
+        doc = self.load_base('unchanged.yml', ['repo'])
+        doc.freeze()
+        doc['int_field'] = 13
+        self.assertEqual(
+            'base:\n'
+            '  $ref: /base\n'
+            '  int_field: 13\n',
+            doc.diff_to_override('/base', [self.fix_get_path('repo')])
+        )
+
+    def test_diff_to_override_subdoc_options(self):
+        doc = Commands.from_yaml(self.fix_get_path('commands.yml'))
+        doc.resolve_and_merge_references([])
+        override = doc.diff_to_override('/commands', [self.fix_get_path('repo')])
+        # Neither $index nor $key are part of the override, they are inserted again when it is loaded.
+        self.assertEqual(
+            'commands:\n'
+            '  $ref: /commands\n'
+            '  indexed:\n'
+            '  - run: second\n'
+            '  keyed:\n'
+            '    stop:\n'
+            '      run: stop\n'
+            '  plain:\n'
+            '    restart:\n'
+            '      run: restart\n',
+            override
+        )
+
+    def test_diff_to_override_invalid_list(self):
+        doc = self.load_base('reordered.yml', [])
+        with self.assertRaisesRegex(ValueError, 'list at more.list'):
+            doc.diff_to_override('/base', [self.fix_get_path('repo')])
+
@@ -0,0 +1,67 @@
+import json
+import tomllib
+
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base
+
+
+class Dump(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'dump'
+
+    def load(self):
+        return self.load_base('base.yml', [])
+
+    def test_to_yaml(self):
+        doc = self.load()
+        with open(self.fix_get_path('base.yml')) as f:
+            self.assertEqual(f.read(), doc.to_yaml())
+
+    def test_to_yaml_options(self):
+        doc = self.load()
+        self.assertEqual(
+            'int_field: 12\n'
+            'level_direct: !Level\n'
+            '  name: direct\n'
+            'more:\n'
+            '  alpha:\n'
+            '  - 1\n'
+            '  - 2.5\n'
+            '  - true\n'
+            '  zeta: 1\n'
+            'str_field: hello\n',
+            doc.to_yaml(header=False, subdocument_tags=True, sort_keys=True)
+        )
+
+    def test_to_yaml_round_trip(self):
+        doc = self.load()
+        self.assertEqual(doc.to_dict(), Base.from_yaml_str(doc.to_yaml()).to_dict())
+
+    def test_to_yaml_frozen(self):
+        doc = self.load()
+        doc.freeze()
+        doc['str_field'] = 'changed'
+        self.assertIn('str_field: changed', doc.to_yaml())
+
+    def test_to_json(self):
+        doc = self.load()
+        self.assertEqual(doc.to_dict(), json.loads(doc.to_json()))
+        self.assertEqual(
+            '{"int_field":12,"level_direct":{"name":"direct"},"more":{"alpha":[1,2.5,true],"zeta":1},"str_field":"hello"}',
+            doc.to_json(header=False, sort_keys=True)
+        )
+        self.assertEqual(
+            json.dumps(doc.to_dict(), indent=2),
+            doc.to_json(indent=2)
+        )
+
+    def test_to_toml(self):
+        doc = self.load()
+        self.assertEqual(doc.to_dict(), tomllib.loads(doc.to_toml()))
+        self.assertEqual(doc.to_dict()['base'], tomllib.loads(doc.to_toml(header=False, sort_keys=True)))
+
+    def test_to_toml_none(self):
+        doc = Base.from_yaml_str('base:\n  str_field: null\n')
+        with self.assertRaisesRegex(ValueError, 'Unable to convert the document to TOML'):
+            doc.to_toml()
@@ -0,0 +1,117 @@
+import json
+import os
+import shutil
+import tempfile
+
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base
+
+
+class EditSource(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'edit_source'
+
+    def setUp(self):
+        super().setUp()
+        self.tmp = tempfile.mkdtemp()
+        shutil.copytree(self.fix_get_path(''), self.tmp, dirs_exist_ok=True)
+
+    def tearDown(self):
+        shutil.rmtree(self.tmp)
+
+    def path(self, path):
+        return os.path.join(self.tmp, path)
+
+    def load(self, path='base.yml'):
+        doc = Base.from_yaml(self.path(path))
+        doc.resolve_and_merge_references([self.path('repo')])
+        return doc
+
+    def read(self, path='base.yml'):
+        with open(self.path(path)) as f:
+            return f.read()
+
+    def test_set_existing(self):
+        self.assertEqual(self.path('base.yml'), self.load().set_in_source(['more', 'php'], '8.1'))
+        self.assertEqual(
+            "    php: '8.1'  # the PHP version\n",
+            self.read().splitlines(keepends=True)[5]
+        )
+        self.assertEqual('8.1', self.load().to_dict()['base']['more']['php'])
+
+    def test_set_in_referenced_file(self):
+        self.assertEqual(
+            self.path('repo/folder/reference.yml'),
+            self.load().set_in_source(['int_field'], 13)
+        )
+        self.assertEqual('base:\n  int_field: 13 # from reference\n', self.read('repo/folder/reference.yml'))
+
+    def test_set_with_source(self):
+        self.load().set_in_source(['int_field'], 13, source=self.path('base.yml'))
+        self.assertIn('  int_field: 13\n    # last comment\n', self.read())
+        self.assertEqual(13, self.load().to_dict()['base']['int_field'])
+
+    def test_add_keys(self):
+        doc = self.load()
+        doc.set_in_source(['more', 'version'], 2)
+        doc.set_in_source(['more', 'new', 'deep'], True)
+        self.assertTrue(self.read().endswith(
+            '      line\n'
+            '    version: 2\n'
+            '    new:\n'
+            '      deep: true\n'
+            '    # last comment\n'
+        ))
+        more = self.load().to_dict()['base']['more']
+        self.assertEqual(2, more['version'])
+        self.assertEqual({'deep': True}, more['new'])
+
+    def test_set_empty_values(self):
+        doc = self.load('empty.yml')
+        doc.set_in_source(['str_field'], 'hello')
+        doc.set_in_source(['more', 'tilde'], 1)
+        doc.set_in_source(['more', 'null'], 2)
+        doc.set_in_source(['more', 'comment'], 3)
+        doc.set_in_source(['more', 'list', 0], 'a')
+        self.assertEqual(
+            'base:\n'
+            '  str_field: hello\n'
+            '  more:\n'
+            '    tilde: 1\n'
+            '    null: 2\n'
+            '    comment: 3  # no value yet\n'
+            '    list:\n'
+            '      - a\n'
+            '      - b\n',
+            self.read('empty.yml')
+        )
+
+    def test_add_to_flow_dict(self):
+        self.load().set_in_source(['more', 'flow', 'y'], 'two')
+        self.assertIn('    flow: {x: 1, y: two}\n', self.read())
+
+    def test_replace_collections(self):
+        doc = self.load()
+        doc.set_in_source(['more', 'list', 1], 'z')
+        self.assertIn('      - a\n      - z\n', self.read())
+        doc.set_in_source(['more', 'list'], ['c', {'d': 1}])
+        doc.set_in_source(['more', 'text'], 'single')
+        self.assertIn('    text: single\n    # last comment\n', self.read())
+        more = self.load().to_dict()['base']['more']
+        self.assertEqual(['c', {'d': 1}], more['list'])
+        self.assertEqual('single', more['text'])
+        self.assertEqual({'x': 1}, more['flow'])
+
+    def test_not_a_dict(self):
+        with self.assertRaisesRegex(ValueError, 'the value at base/str_field is not a dict'):
+            self.load().set_in_source(['str_field', 'x'], 1)
+
+    def test_json(self):
+        doc = self.load('base.json')
+        doc.set_in_source(['str_field'], 'changed')
+        doc.set_in_source(['more', 'list'], [1])
+        self.assertEqual(
+            {'base': {'str_field': 'changed', 'more': {'list': [1]}}},
+            json.loads(self.read('base.json'))
+        )
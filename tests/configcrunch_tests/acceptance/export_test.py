@@ -0,0 +1,85 @@
+import os
+from tempfile import TemporaryDirectory
+
+from configcrunch import InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Level, Stack
+
+
+class Export(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'export'
+
+    def setUp(self):
+        super().setUp()
+        self.tmp = TemporaryDirectory()
+        self.target = os.path.join(self.tmp.name, 'exported.yml')
+
+    def tearDown(self):
+        self.tmp.cleanup()
+
+    def load(self):
+        return self.load_base('base.yml', ['repo'])
+
+    def test_export(self):
+        doc = self.load()
+        doc.export(self.target)
+        with open(self.target) as f:
+            content = f.read()
+        self.assertIn('level_direct: !Level', content)
+        self.assertNotIn('$ref', content)
+
+        exported = Base.from_yaml(self.target)
+        self.assertEqual(doc.to_dict(), exported.to_dict())
+        exported.resolve_and_merge_references([])
+        self.assertEqual(doc.to_dict(), exported.to_dict())
+        exported.freeze()
+        self.assertIsInstance(exported['level_direct'], Level)
+        self.assertIsInstance(exported['level_dict']['merged'], Level)
+        self.assertEqual('base_merge', exported['level_dict']['merged']['more']['from'])
+
+    def test_export_before_variables(self):
+        doc = self.load()
+        doc.export(self.target)
+        with open(self.target) as f:
+            self.assertIn('{{ more.name }}', f.read())
+
+        exported = Base.from_yaml(self.target).resolve_and_merge_references([])
+        exported.process_vars()
+        doc.process_vars()
+        self.assertEqual(doc.to_dict(), exported.to_dict())
+        exported.freeze()
+        self.assertEqual('exported', exported['level_direct']['name'])
+
+    def test_export_after_variables(self):
+        doc = self.load()
+        doc.process_vars()
+        doc.export(self.target)
+        with open(self.target) as f:
+            content = f.read()
+        self.assertNotIn('{{', content)
+        self.assertEqual(doc.to_dict(), Base.from_yaml(self.target).to_dict())
+
+    def test_other_class_tags(self):
+        # Only the classes of Base and its sub-documents are removed, Stack is not one of them
+        with self.assertRaisesRegex(InvalidDocumentError, 'Unknown YAML tag !Stack'):
+            Base.from_yaml_str('base:\n  more: !Stack {}\n')
+        with self.assertRaisesRegex(InvalidDocumentError, 'Unknown YAML tag !Command'):
+            Stack.from_yaml_str('stack:\n  primary: !Command {}\n')
+
+    def test_export_discriminated(self):
+        doc = Stack.from_dict({'primary': {'type': 'postgres'}, 'databases': {'db': {'type': 'mysql'}}})
+        doc.resolve_and_merge_references([])
+        doc.export(self.target)
+        with open(self.target) as f:
+            content = f.read()
+        self.assertIn('!Postgres', content)
+        self.assertIn('!Mysql', content)
+        self.assertEqual(doc.to_dict(), Stack.from_yaml(self.target).to_dict())
+
+    def test_export_unresolved(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        with self.assertRaisesRegex(ValueError, 'resolve_and_merge_references'):
+            doc.export(self.target)
+        self.assertFalse(os.path.exists(self.target))
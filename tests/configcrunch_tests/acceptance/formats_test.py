@@ -0,0 +1,44 @@
+from configcrunch import list_documents, InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base
+
+
+class Formats(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'formats'
+
+    def test_json(self):
+        doc = Base.from_yaml(self.fix_get_path('base.json'))
+        self.assertEqual({
+            'base': {
+                'str_field': 'json',
+                'int_field': 1,
+                'more': {'null': None, 'bool': True, 'float': 1.5, 'list': [1, 'two']}
+            }
+        }, doc.to_dict())
+
+    def test_toml(self):
+        doc = Base.from_yaml(self.fix_get_path('base.toml'))
+        self.assertEqual({
+            'base': {
+                'str_field': 'toml',
+                'int_field': 2,
+                'more': {'date': '2024-01-02', 'datetime': '2024-01-02T03:04:05Z', 'bool': False, 'list': [1, 'two']}
+            }
+        }, doc.to_dict())
+
+    def test_ref_merges_formats(self):
+        self.assertDocEqualMerging(
+            'ref_expected.yml',
+            'ref.yml',
+            ['repo']
+        )
+
+    def test_list_documents(self):
+        repo = self.fix_get_path('repo')
+        self.assertEqual([('/layered', [repo])], list_documents(Base, [repo]))
+
+    def test_invalid(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('invalid.json'))
@@ -0,0 +1,44 @@
+from configcrunch import list_documents, InvalidHeaderError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Renamed, Plain
+
+
+class HeaderVariants(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'header_variants'
+
+    def load(self, doc_type, path):
+        doc = doc_type.from_yaml(self.fix_get_path(path))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertTrue(doc.validate())
+        return doc
+
+    def test_header(self):
+        self.assertEqual({'renamed': {'value': 'new'}}, self.load(Renamed, 'renamed.yml').to_dict())
+
+    def test_header_alias(self):
+        self.assertEqual(
+            {'renamed': {'value': 'old', 'more': 'from legacy base'}},
+            self.load(Renamed, 'legacy.yml').to_dict()
+        )
+
+    def test_header_alias_invalid(self):
+        with self.assertRaises(InvalidHeaderError):
+            Renamed.from_yaml(self.fix_get_path('other.yml'))
+
+    def test_header_alias_list_documents(self):
+        repo = self.fix_get_path('repo')
+        self.assertEqual([
+            ('/legacy_base', [repo]),
+            ('/renamed_base', [repo]),
+        ], list_documents(Renamed, [repo]))
+
+    def test_headerless(self):
+        self.assertEqual(
+            {'plain': {'value': 'plain', 'more': 'from plain base'}},
+            self.load(Plain, 'plain.yml').to_dict()
+        )
+
+    def test_headerless_with_header(self):
+        self.assertEqual({'plain': {'value': 'with header'}}, self.load(Plain, 'plain_with_header.yml').to_dict())
@@ -0,0 +1,32 @@
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+MORE_KEYS = ['zeta', 'alpha', 'mu', 'yankee', 'bravo', 'keys', 'items']
+
+
+class KeyOrder(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'key_order'
+
+    def load(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.process_vars()
+        doc.freeze()
+        return doc
+
+    def test_merged_order(self):
+        # Keys of the referenced document come first, new keys are appended in their order
+        doc = self.load()
+        self.assertEqual(['str_field', 'more', 'int_field'], list(doc.to_dict()['base'].keys()))
+        self.assertEqual(MORE_KEYS, list(doc.to_dict()['base']['more'].keys()))
+
+    def test_frozen_order(self):
+        doc = self.load()
+        self.assertEqual(['str_field', 'more', 'int_field'], list(doc))
+        self.assertEqual(MORE_KEYS, list(doc['more']))
+
+    def test_template_order(self):
+        doc = self.load()
+        self.assertEqual(','.join(MORE_KEYS), doc['more']['keys'])
+        self.assertEqual(''.join(MORE_KEYS), doc['more']['items'])
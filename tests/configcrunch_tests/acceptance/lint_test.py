@@ -0,0 +1,58 @@
+import io
+from contextlib import redirect_stderr, redirect_stdout
+from os.path import join
+
+from schema import SchemaError
+
+from configcrunch import lint_documents, ReferencedDocumentNotFound, VariableProcessingError
+from configcrunch.__main__ import main
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Level
+
+
+class Lint(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'lint'
+
+    def test_lint_documents(self):
+        repo = self.fix_get_path('repo')
+        failures = lint_documents([Base, Level], [repo])
+        self.assertEqual([
+            ('/base/missing_ref', [join(repo, 'base', 'missing_ref.yml')], ReferencedDocumentNotFound),
+            ('/base/schema_error', [join(repo, 'base', 'schema_error.yml')], SchemaError),
+            ('/base/variable_error', [join(repo, 'base', 'variable_error.yml')], VariableProcessingError),
+            ('/level/no_name', [join(repo, 'level', 'no_name.yml')], SchemaError),
+        ], [(ref_path, files, type(error)) for ref_path, files, error in failures])
+
+    def test_lint_documents_valid(self):
+        self.assertEqual([], lint_documents([Level], [self.fix_get_path('valid_only')]))
+
+    def test_lint_command(self):
+        stdout = io.StringIO()
+        stderr = io.StringIO()
+        with redirect_stdout(stdout), redirect_stderr(stderr):
+            exit_code = main([
+                'lint',
+                '-t', 'configcrunch_tests.fixtures.documents.Base',
+                '-t', 'configcrunch_tests.fixtures.documents.Level',
+                '-l', self.fix_get_path('repo')
+            ])
+        self.assertEqual(1, exit_code)
+        self.assertEqual("4 invalid document(s) found.\n", stdout.getvalue())
+        self.assertIn('/base/missing_ref', stderr.getvalue())
+        self.assertIn('/level/no_name', stderr.getvalue())
+
+    def test_cli_invalid_type(self):
+        for name, message in [
+            ('configcrunch_tests.fixtures.missing.Base', 'Unable to import configcrunch_tests.fixtures.missing.Base'),
+            ('configcrunch_tests.fixtures.documents.Missing', 'Unable to import configcrunch_tests.fixtures.documents.Missing'),
+            ('configcrunch_tests.fixtures.documents.Schema', 'configcrunch_tests.fixtures.documents.Schema is not a YamlConfigDocument class'),
+        ]:
+            with self.subTest(name):
+                stderr = io.StringIO()
+                with redirect_stderr(stderr), self.assertRaises(SystemExit) as cm:
+                    main(['lint', '-t', name, '-l', self.fix_get_path('repo')])
+                self.assertEqual(2, cm.exception.code)
+                self.assertIn('usage: configcrunch lint', stderr.getvalue())
+                self.assertIn(message, stderr.getvalue())
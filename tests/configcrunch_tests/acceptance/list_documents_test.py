@@ -0,0 +1,24 @@
+from configcrunch import list_documents
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Level
+
+
+class ListDocuments(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'list_documents'
+
+    def test_list_documents(self):
+        repo1 = self.fix_get_path('repo1')
+        repo2 = self.fix_get_path('repo2')
+        self.assertEqual([
+            ('/apps/nested/three', [repo2]),
+            ('/apps/one', [repo1, repo2]),
+            ('/two', [repo1]),
+        ], list_documents(Base, [repo1, repo2]))
+        self.assertEqual([
+            ('/apps/level', [repo1]),
+        ], list_documents(Level, [repo1, repo2]))
+
+    def test_list_documents_missing_repo(self):
+        self.assertEqual([], list_documents(Base, [self.fix_get_path('does_not_exist')]))
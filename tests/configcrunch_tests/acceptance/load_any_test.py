@@ -0,0 +1,52 @@
+from configcrunch import load_any, register_document_type, registered_document_types, InvalidHeaderError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Level, Renamed
+
+
+class LoadAny(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'load_any'
+
+    def setUp(self):
+        super().setUp()
+        self.assertIs(Base, register_document_type(Base))
+        register_document_type(Level)
+
+    def test_registered_document_types(self):
+        types = registered_document_types()
+        self.assertIs(Base, types['base'])
+        self.assertIs(Level, types['level'])
+
+    def test_register_conflicting_header(self):
+        class OtherLevel(Level):
+            pass
+
+        with self.assertRaises(ValueError):
+            register_document_type(OtherLevel)
+        self.assertIs(Level, registered_document_types()['level'])
+
+    def test_load_any(self):
+        base = load_any(self.fix_get_path('base.yml'))
+        self.assertIsInstance(base, Base)
+        self.assertEqual({'base': {'str_field': 'base'}}, base.to_dict())
+        self.assertEqual([self.fix_get_path('base.yml')], base.absolute_paths)
+
+        level = load_any(self.fix_get_path('level.yml'))
+        self.assertIsInstance(level, Level)
+        self.assertEqual({'level': {'name': 'level'}}, level.to_dict())
+
+    def test_load_any_unknown_header(self):
+        with self.assertRaises(InvalidHeaderError):
+            load_any(self.fix_get_path('unknown.yml'))
+
+    def test_load_any_ambiguous_header(self):
+        with self.assertRaises(InvalidHeaderError):
+            load_any(self.fix_get_path('ambiguous.yml'))
+
+    def test_load_any_header_alias(self):
+        register_document_type(Renamed)
+        self.assertIs(Renamed, registered_document_types()['legacy'])
+        doc = load_any(self.fix_get_path('legacy.yml'))
+        self.assertIsInstance(doc, Renamed)
+        self.assertEqual({'renamed': {'value': 'old'}}, doc.to_dict())
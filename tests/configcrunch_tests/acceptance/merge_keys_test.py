@@ -0,0 +1,24 @@
+from configcrunch import InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class MergeKeys(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merge_keys'
+
+    def test_merge_keys(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'merge_keys.yml',
+            []
+        )
+
+    def test_merge_keys_order(self):
+        # Like in PyYAML, the merged keys come first, followed by the keys of the mapping in their order
+        web = self.load_base('order.yml', []).to_dict()['base']['more']['web']
+        self.assertEqual(['image', 'restart', 'name', 'user', 'port'], list(web.keys()))
+
+    def test_invalid_merge_key(self):
+        with self.assertRaisesRegex(InvalidDocumentError, '<<'):
+            self.load_base('invalid.yml', [])
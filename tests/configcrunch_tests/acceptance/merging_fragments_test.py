@@ -0,0 +1,29 @@
+from configcrunch import CircularDependencyError, ReferencedDocumentNotFound
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class MergingFragments(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_fragments'
+
+    def test_fragments(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_fragments_diamond(self):
+        self.assertDocEqualMerging(
+            'expected_diamond.yml',
+            'base_diamond.yml',
+            ['repo']
+        )
+
+    def test_fragment_missing(self):
+        self.assertRaises(ReferencedDocumentNotFound, self.load_base, 'base_missing.yml', ['repo'])
+
+    def test_fragment_circular(self):
+        with self.assertRaisesRegex(CircularDependencyError, '/loop'):
+            self.load_base('base_loop.yml', ['repo'])
@@ -0,0 +1,34 @@
+from configcrunch import CircularDependencyError, InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class MergingMixins(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_mixins'
+
+    def test_mixins(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_mixins_circular(self):
+        self.assertRaises(CircularDependencyError, self.load_base, 'circular.yml', ['repo'])
+
+    def test_invalid_ref_type(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'Expected a reference or a list of references, got: 12'):
+            self.load_base('invalid_ref_type.yml', ['repo'])
+
+    def test_invalid_ref_entry(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'Invalid entry in the \\$ref list'):
+            self.load_base('invalid_ref_entry.yml', ['repo'])
+
+    def test_empty_ref_list(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'Expected a reference or a list of references'):
+            self.load_base('empty_ref_list.yml', ['repo'])
+
+    def test_invalid_ref_in_fragment_reference(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'repo/invalid: Expected a reference or a list of references'):
+            self.load_base('invalid_ref_in_fragment.yml', ['repo'])
@@ -0,0 +1,31 @@
+from configcrunch import ReferencedDocumentNotFound, VariableProcessingError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+from configcrunch_tests.fixtures.documents import Base
+
+
+class MergingRefTemplates(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_ref_templates'
+
+    def test_ref_templates(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references(
+            [self.fix_get_path('repo')], {"environment": "prod", "kind": "flavour_b"}
+        )
+        expected_result = self.fix_get_yml('expected.yml')
+
+        self.assertDictEqual(deep_sort(expected_result), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
+
+    def test_ref_templates_not_enabled(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        self.assertRaises(
+            ReferencedDocumentNotFound, doc.resolve_and_merge_references, [self.fix_get_path('repo')]
+        )
+
+    def test_ref_templates_unresolvable(self):
+        doc = Base.from_yaml(self.fix_get_path('base_unresolvable.yml'))
+        self.assertRaises(
+            VariableProcessingError, doc.resolve_and_merge_references, [self.fix_get_path('repo')], {}
+        )
@@ -0,0 +1,23 @@
+from configcrunch import InvalidDocumentError, ReferencedDocumentNotFound
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class MergingWildcard(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_wildcard'
+
+    def test_wildcard(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo', 'repo2']
+        )
+
+    def test_wildcard_no_match(self):
+        with self.assertRaisesRegex(ReferencedDocumentNotFound, '/nothing/\\*'):
+            self.load_base('no_match.yml', ['repo', 'repo2'])
+
+    def test_wildcard_in_directory(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'only supported in the file name'):
+            self.load_base('wildcard_directory.yml', ['repo', 'repo2'])
@@ -0,0 +1,35 @@
+from configcrunch import load_all, register_document_type, InvalidHeaderError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Level, Renamed
+
+
+class MultiType(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'multi_type'
+
+    def setUp(self):
+        super().setUp()
+        register_document_type(Base)
+        register_document_type(Level)
+        register_document_type(Renamed)
+
+    def test_ref_to_multi_type_file(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_load_all(self):
+        documents = load_all(self.fix_get_path('bundle.yml'))
+        self.assertEqual(['base', 'level'], sorted(documents.keys()))
+        self.assertIsInstance(documents['base'], Base)
+        self.assertIsInstance(documents['level'], Level)
+        self.assertEqual({'name': 'level in bundle'}, documents['level'].to_dict()['level'])
+        documents['base'].resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertIsInstance(documents['base'].to_dict()['base']['level_direct'], dict)
+
+    def test_load_all_duplicate_type(self):
+        with self.assertRaises(InvalidHeaderError):
+            load_all(self.fix_get_path('duplicate.yml'))
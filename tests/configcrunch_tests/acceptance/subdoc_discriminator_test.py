@@ -0,0 +1,78 @@
+from configcrunch import CircularDependencyError, InvalidDocumentError, InvalidHeaderError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Stack, Mysql, Postgres, Tree
+
+
+class SubdocDiscriminator(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'subdoc_discriminator'
+
+    def load_stack(self, path):
+        doc = Stack.from_yaml(self.fix_get_path(path))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        return doc
+
+    def test_discriminator(self):
+        doc = self.load_stack('stack.yml')
+        doc.validate()
+        with doc.internal_access():
+            self.assertIsInstance(doc['databases']['users'], Mysql)
+            self.assertIsInstance(doc['databases']['events'], Postgres)
+            self.assertEqual({'$name': 'events', 'type': 'postgres', 'extensions': ['timescaledb']},
+                             doc['databases']['events'].doc)
+
+    def test_discriminator_from_reference(self):
+        doc = self.load_stack('stack.yml')
+        with doc.internal_access():
+            self.assertIsInstance(doc['databases']['cache'], Mysql)
+            self.assertEqual({'$name': 'cache', 'type': 'mysql', 'engine': 'myisam'},
+                             doc['databases']['cache'].doc)
+            self.assertIsInstance(doc['primary'], Mysql)
+            self.assertEqual({'type': 'mysql', 'engine': 'memory'}, doc['primary'].doc)
+
+    def test_discriminator_from_reference_diamond(self):
+        doc = self.load_stack('diamond.yml')
+        with doc.internal_access():
+            self.assertIsInstance(doc['databases']['shared'], Mysql)
+            self.assertEqual({'$name': 'shared', 'type': 'mysql', 'engine': 'y'},
+                             doc['databases']['shared'].doc)
+
+    def test_unknown_discriminator(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'oracle'):
+            self.load_stack('unknown.yml')
+
+    def test_missing_discriminator(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'missing the discriminator'):
+            self.load_stack('missing.yml')
+
+    def test_discriminator_invalid_reference(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'Invalid entry in the \\$ref list'):
+            self.load_stack('invalid_ref.yml')
+
+    def test_discriminator_from_multi_type_file(self):
+        doc = self.load_stack('multi.yml')
+        with doc.internal_access():
+            self.assertIsInstance(doc['databases']['multi'], Mysql)
+            self.assertEqual({'$name': 'multi', 'type': 'mysql', 'engine': 'multi'},
+                             doc['databases']['multi'].doc)
+
+    def test_discriminator_from_ambiguous_file(self):
+        with self.assertRaisesRegex(InvalidHeaderError, 'exactly one of the headers \\["mysql", "postgres"\\]'):
+            self.load_stack('ambiguous.yml')
+
+    def test_discriminator_circular_reference(self):
+        # The discriminator is looked up in the references, which reference each other
+        with self.assertRaises(CircularDependencyError):
+            self.load_stack('loop.yml')
+
+    def test_discriminated_circular_reference(self):
+        # The type is known, the references are loaded as typed documents
+        with self.assertRaises(CircularDependencyError):
+            self.load_stack('loop_typed.yml')
+
+    def test_circular_reference_through_discriminated_subdocument(self):
+        # The referenced tree contains a discriminated subdocument that references the tree again
+        doc = Tree.from_yaml(self.fix_get_path('tree_loop.yml'))
+        with self.assertRaises(CircularDependencyError):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
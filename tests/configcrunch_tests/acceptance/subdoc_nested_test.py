@@ -0,0 +1,45 @@
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Nested, Level, Base
+
+
+class SubdocNested(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'subdoc_nested'
+
+    def load_nested(self):
+        doc = Nested.from_yaml(self.fix_get_path('nested.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        doc.validate()
+        doc.freeze()
+        return doc
+
+    def test_nested_collections(self):
+        doc = self.load_nested()
+        one = doc['groups']['one']['levels']
+        self.assertIsInstance(one[0], Level)
+        self.assertEqual('one_a', one[0]['name'])
+        self.assertIsInstance(one[1], Level)
+        self.assertEqual('referenced', one[1]['name'])
+        self.assertIsInstance(doc['groups']['two']['levels'][0], Level)
+        self.assertEqual({}, doc['groups']['empty'])
+
+    def test_nested_in_subdocuments(self):
+        doc = self.load_nested()
+        level = doc['levels'][0]
+        self.assertIsInstance(level, Level)
+        self.assertIsInstance(level['more'], Base)
+        self.assertEqual('more base', level['more']['str_field'])
+
+    def test_missing_collections(self):
+        doc = Nested.from_yaml(self.fix_get_path('missing.yml'))
+        doc.resolve_and_merge_references([])
+        doc.validate()
+        doc.freeze()
+        self.assertEqual({'without_levels': {}}, doc['groups'])
+        self.assertNotIn('levels', doc)
+
+        doc = Nested.from_dict({})
+        doc.resolve_and_merge_references([])
+        doc.freeze()
+        self.assertEqual({}, doc.to_dict()['nested'])
@@ -0,0 +1,37 @@
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Commands, Command
+
+
+class SubdocOptions(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'subdoc_options'
+
+    def test_options(self):
+        doc = Commands.from_yaml(self.fix_get_path('commands.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertTrue(doc.validate())
+        self.assertEqual({
+            'commands': {
+                'indexed': [
+                    {'$index': 0, 'run': 'first'},
+                    {'$index': 1, 'run': 'second'},
+                ],
+                'keyed': {
+                    'start': {'$key': 'start', 'run': 'start'},
+                },
+                'plain': {
+                    'stop': {'run': 'stop'},
+                },
+            }
+        }, doc.to_dict())
+
+    def test_invalid_option(self):
+        class InvalidOption(Commands):
+            @classmethod
+            def subdocuments(cls):
+                return [("indexed[]", Command, {"unknown": "$index"})]
+
+        doc = InvalidOption.from_yaml(self.fix_get_path('commands.yml'))
+        with self.assertRaisesRegex(ValueError, 'unknown'):
+            doc.resolve_and_merge_references([])
@@ -162,3 +162,40 @@ class SubdocSpecsTest(unittest.TestCase):
             }, type),
             _test__subdoc_specs("level1/level2/dict2[]", type, FIXTURE, "REPLACED")
         )
+
+    def test_subdoc_specs_nested_collections(self):
+        fixture = {
+            "services": {
+                "web": {"ports": ["80", "443"]},
+                "db": {"ports": ["5432"]},
+                "worker": {}
+            },
+            "list": [{"ports": ["1"]}, {"ports": ["2", "3"]}]
+        }
+        expected_services = {
+            "services": {
+                "web": {"ports": ["REPLACED", "REPLACED"]},
+                "db": {"ports": ["REPLACED"]},
+                "worker": {}
+            },
+            "list": [{"ports": ["1"]}, {"ports": ["2", "3"]}]
+        }
+        self.assertEqual(
+            (expected_services, type),
+            _test__subdoc_specs("services[]/ports[]", type, fixture, "REPLACED")
+        )
+        self.assertEqual(
+            (expected_services, type),
+            _test__subdoc_specs("services/*/ports[]", type, fixture, "REPLACED")
+        )
+        self.assertEqual(
+            ({
+                "services": fixture["services"],
+                "list": [{"ports": "REPLACED"}, {"ports": "REPLACED"}]
+            }, type),
+            _test__subdoc_specs("list[]/ports", type, fixture, "REPLACED")
+        )
+        with self.assertRaises(ValueError):
+            _test__subdoc_specs("*/ports", type, fixture, "REPLACED")
+        with self.assertRaises(ValueError):
+            _test__subdoc_specs("services/*/*", type, fixture, "REPLACED")
@@ -1,3 +1,4 @@
+from configcrunch import VariableProcessingError
 from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
 
 
@@ -36,6 +37,36 @@ class Variables(ConfigcrunchTestCase):
             'complex.yml'
         )
 
+    def test_conditionals(self):
+        self.assertDocEqualVariables(
+            'expected/conditionals.yml',
+            'conditionals.yml'
+        )
+
+    def test_for_each(self):
+        self.assertDocEqualVariables(
+            'expected/for_each.yml',
+            'for_each.yml'
+        )
+
+    def test_for_each_templated(self):
+        self.assertDocEqualVariables(
+            'expected/for_each_templated.yml',
+            'for_each_templated.yml'
+        )
+
+    def test_for_each_outside_of_lists(self):
+        for fixture, key in [
+            ('for_each_in_dict.yml', r'\$for_each'),
+            ('for_each_at_root.yml', r'\$for_each'),
+            ('for_each_as_only.yml', r'\$as'),
+            ('for_each_in_item.yml', r'\$for_each'),
+        ]:
+            with self.subTest(fixture):
+                doc = self.load_base(fixture, [])
+                with self.assertRaisesRegex(VariableProcessingError, key + ' is only allowed in list entries'):
+                    doc.process_vars()
+
     def test_not_working(self):
         """
         When calling parent() and accessing a field on the parent with variables in it,
@@ -0,0 +1,78 @@
+from configcrunch import register_tag_handler, InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, StrictKeysBase, Yaml11Base
+
+
+class YamlKeys(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'yaml_keys'
+
+    def load_more(self, path):
+        return self.load_base(path, []).to_dict()['base']['more']
+
+    def test_non_string_keys(self):
+        self.assertEqual({
+            'ports': {'80': 8080, '443': '8443'},
+            'keys': {'true': 'bool', '1.5': 'float', 'null': None, '80': 'string'},
+        }, self.load_more('ports.yml'))
+
+    def test_non_string_keys_rejected(self):
+        with self.assertRaisesRegex(InvalidDocumentError, "Invalid key '80'"):
+            StrictKeysBase.from_yaml(self.fix_get_path('ports.yml'))
+        # Other document types are not affected
+        self.load_base('ports.yml', [])
+
+    def test_non_string_keys_rejected_in_references(self):
+        doc = StrictKeysBase.from_yaml(self.fix_get_path('ref_ports.yml'))
+        with self.assertRaisesRegex(InvalidDocumentError, "Invalid key '80'"):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_failing_python_key(self):
+        class Key:
+            def __str__(self):
+                raise RuntimeError('no string')
+
+        register_tag_handler('!failing', lambda value, path: {Key(): 'value'})
+        with self.assertRaisesRegex(RuntimeError, 'no string'):
+            Base.from_yaml_str('base:\n  more: !failing\n')
+
+    def test_duplicate_keys_after_conversion(self):
+        with self.assertRaisesRegex(InvalidDocumentError, "Duplicate key '80'"):
+            self.load_base('duplicate.yml', [])
+
+    def test_complex_keys(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'Keys must be strings, numbers, booleans or null'):
+            self.load_base('complex.yml', [])
+
+    def test_python_keys(self):
+        doc = Base.from_dict({'more': {80: 8080, True: 'bool', None: None, 1.5: 'float'}})
+        doc.freeze()
+        self.assertEqual(
+            {'80': 8080, 'true': 'bool', 'null': None, '1.5': 'float'},
+            doc.to_dict()['base']['more']
+        )
+
+    def test_tag_handler_values(self):
+        register_tag_handler('!values', lambda value, path: {80: True, 'none': None, 'int': 1})
+        doc = Base.from_yaml_str('base:\n  more: !values\n')
+        doc.freeze()
+        self.assertEqual({'80': True, 'none': None, 'int': 1}, doc.to_dict()['base']['more'])
+
+    def test_yaml12_booleans(self):
+        more = self.load_more('yaml11.yml')
+        self.assertEqual(['yes', 'No', 'ON', 'off'], more['plain'])
+        self.assertEqual('key', more['yes'])
+
+    def test_yaml11_booleans(self):
+        doc = Yaml11Base.from_yaml(self.fix_get_path('yaml11.yml'))
+        self.assertEqual({
+            'str_field': 'yes',
+            'more': {
+                'plain': [True, False, True, False],
+                'quoted': ['yes', 'off', 'on'],
+                'other': ['y', 'yesterday', True, 'no\n'],
+                'true': 'key',
+                'größe': True,
+            }
+        }, doc.to_dict()['base'])
@@ -0,0 +1,35 @@
+from configcrunch import list_documents, InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base, Level
+
+
+class YamlStreams(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'yaml_streams'
+
+    def test_layers_merged(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'layers.yml',
+            []
+        )
+
+    def test_stream_index(self):
+        doc = Base.from_yaml(self.fix_get_path('layers.yml'), stream_index=1)
+        self.assertEqual(self.fix_get_yml('second.yml'), doc.to_dict())
+
+    def test_stream_index_out_of_range(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('layers.yml'), stream_index=3)
+
+    def test_ref_to_stream(self):
+        self.assertDocEqualMerging(
+            'ref_expected.yml',
+            'ref.yml',
+            ['repo']
+        )
+
+    def test_list_documents_in_stream(self):
+        repo = self.fix_get_path('repo')
+        self.assertEqual([('/generated', [repo])], list_documents(Level, [repo]))
@@ -0,0 +1,54 @@
+import io
+
+from configcrunch import InvalidDocumentError, InvalidHeaderError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import Base
+
+
+class YamlStrings(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'yaml_strings'
+
+    def test_from_yaml_str(self):
+        doc = Base.from_yaml_str('base:\n  str_field: from string\n  int_field: 1\n')
+        self.assertEqual({'base': {'str_field': 'from string', 'int_field': 1}}, doc.to_dict())
+        self.assertEqual([], doc.absolute_paths)
+
+    def test_from_yaml_str_origin_name(self):
+        doc = Base.from_yaml_str('base:\n  str_field: x\n', origin_name='inline.yml')
+        self.assertEqual(['inline.yml'], doc.absolute_paths)
+
+    def test_from_yaml_str_stream(self):
+        text = 'base:\n  str_field: first\n---\nbase:\n  str_field: second\n'
+        self.assertEqual('second', Base.from_yaml_str(text).to_dict()['base']['str_field'])
+        self.assertEqual('first', Base.from_yaml_str(text, stream_index=0).to_dict()['base']['str_field'])
+        with self.assertRaisesRegex(InvalidDocumentError, 'inline.yml has no document at index 2'):
+            Base.from_yaml_str(text, origin_name='inline.yml', stream_index=2)
+
+    def test_from_yaml_str_include_relative_to_origin(self):
+        doc = Base.from_yaml_str('base: !include parts/included.yml\n', origin_name=self.fix_get_path('inline.yml'))
+        self.assertEqual({'str_field': 'included', 'int_field': 2}, doc.to_dict()['base'])
+
+    def test_from_yaml_str_invalid(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'inline.yml'):
+            Base.from_yaml_str('base: [', origin_name='inline.yml')
+        with self.assertRaises(InvalidHeaderError):
+            Base.from_yaml_str('other:\n  str_field: x\n')
+
+    def test_from_yaml_stream_text(self):
+        doc = Base.from_yaml_stream(io.StringIO('base:\n  str_field: text\n'))
+        self.assertEqual({'str_field': 'text'}, doc.to_dict()['base'])
+        self.assertEqual([], doc.absolute_paths)
+
+    def test_from_yaml_stream_bytes(self):
+        doc = Base.from_yaml_stream(io.BytesIO('base:\n  str_field: bytes ü\n'.encode('utf-8')), origin_name='<stdin>')
+        self.assertEqual({'str_field': 'bytes ü'}, doc.to_dict()['base'])
+        self.assertEqual(['<stdin>'], doc.absolute_paths)
+
+    def test_from_yaml_stream_name(self):
+        stream = io.StringIO('base:\n  str_field: named\n')
+        stream.name = 'named.yml'
+        self.assertEqual(['named.yml'], Base.from_yaml_stream(stream).absolute_paths)
+        stream.seek(0)
+        self.assertEqual(['other.yml'], Base.from_yaml_stream(stream, origin_name='other.yml').absolute_paths)
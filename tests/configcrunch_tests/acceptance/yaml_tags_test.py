@@ -0,0 +1,73 @@
+import os
+
+from configcrunch import register_tag_handler, InvalidDocumentError
+from configcrunch_tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch_tests.fixtures.documents import StrictKeysBase
+
+
+class YamlTags(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'yaml_tags'
+
+    def setUp(self):
+        super().setUp()
+        os.environ['CONFIGCRUNCH_TEST_ENV'] = 'from env'
+        os.environ.pop('CONFIGCRUNCH_TEST_UNSET', None)
+        register_tag_handler('!upper', lambda value, path: value.upper())
+
+    def tearDown(self):
+        del os.environ['CONFIGCRUNCH_TEST_ENV']
+
+    def test_tags(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'tags.yml',
+            []
+        )
+
+    def test_tag_handler_args(self):
+        calls = []
+
+        def handler(value, path):
+            calls.append((value, path))
+            return value
+
+        register_tag_handler('upper', handler)
+        self.load_base('tags.yml', [])
+        self.assertEqual([('hello', self.fix_get_path('tags.yml'))], calls)
+
+    def test_tag_handler_keys(self):
+        calls = []
+
+        def handler(value, path):
+            calls.append(value)
+            return value
+
+        register_tag_handler('keys', handler)
+        self.load_base('handler_keys.yml', [])
+        self.assertEqual([{'80': 'http', 'true': 'yes'}], calls)
+        with self.assertRaisesRegex(InvalidDocumentError, "Invalid key '80'"):
+            StrictKeysBase.from_yaml(self.fix_get_path('handler_keys.yml'))
+
+    def test_include_keys(self):
+        doc = self.load_base('include_keys.yml', [])
+        self.assertEqual({'80': 'http'}, doc.to_dict()['base']['more'])
+        with self.assertRaisesRegex(InvalidDocumentError, "Invalid key '80' in .*parts/keys.yml"):
+            StrictKeysBase.from_yaml(self.fix_get_path('include_keys.yml'))
+
+    def test_include_stream(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'parts/stream.yml contains multiple documents'):
+            self.load_base('include_stream.yml', [])
+
+    def test_circular_include(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'circular'):
+            self.load_base('circular.yml', [])
+
+    def test_unknown_tag(self):
+        with self.assertRaisesRegex(InvalidDocumentError, '!unknown'):
+            self.load_base('unknown.yml', [])
+
+    def test_env_unset(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'CONFIGCRUNCH_TEST_UNSET'):
+            self.load_base('env_unset.yml', [])
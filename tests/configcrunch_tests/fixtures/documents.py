@@ -99,3 +99,290 @@ class Level(YamlConfigDocument):
     @variable_helper
     def level_helper_taking_param(self, param: str):
         return f"level_param: {param}"
+
+
+class Stack(YamlConfigDocument):
+    """
+    Stack test document, with polymorphic subdocuments. Example:
+
+    stack:
+        databases:
+            xyz: !Mysql or !Postgres, depending on type
+        primary: !Mysql or !Postgres, depending on type
+    """
+
+    @classmethod
+    def header(cls) -> str:
+        return "stack"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$ref'): str,
+                Optional('databases'): {
+                    str: Or(DocReference(Mysql), DocReference(Postgres))
+                },
+                Optional('primary'): Or(DocReference(Mysql), DocReference(Postgres)),
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        databases = {"mysql": Mysql, "postgres": Postgres}
+        return [
+            ("databases[]", "type", databases),
+            ("primary", "type", databases),
+        ]
+
+
+class Mysql(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "mysql"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$ref'): str,
+                Optional('$name'): str,
+                'type': 'mysql',
+                Optional('engine'): str,
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class Postgres(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "postgres"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$ref'): str,
+                Optional('$name'): str,
+                'type': 'postgres',
+                Optional('extensions'): [str],
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class Tree(YamlConfigDocument):
+    """
+    Tree test document, whose polymorphic subdocuments can be trees themselves. Example:
+
+    tree:
+        kind: tree
+        children:
+            xyz: !Tree, depending on kind
+    """
+
+    @classmethod
+    def header(cls) -> str:
+        return "tree"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$ref'): str,
+                Optional('$name'): str,
+                Optional('kind'): 'tree',
+                Optional('children'): {
+                    str: DocReference(Tree)
+                },
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return [
+            ("children[]", "kind", {"tree": Tree}),
+        ]
+
+
+class Nested(YamlConfigDocument):
+    """
+    Nested test document, that declares subdocuments inside of nested collections. Example:
+
+    nested:
+        groups:
+            xyz:
+                levels:
+                    - !Level
+        levels:
+            - !Level, whose more field is a !Base
+    """
+
+    @classmethod
+    def header(cls) -> str:
+        return "nested"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('groups'): {
+                    str: {Optional('levels'): [DocReference(Level)]}
+                },
+                Optional('levels'): [DocReference(Level)],
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return [
+            ("groups/*/levels[]", Level),
+            ("levels[]", Level),
+            ("levels[]/more", Base),
+        ]
+
+
+class Commands(YamlConfigDocument):
+    """
+    Commands test document, with options for the keys inserted into subdocuments. Example:
+
+    commands:
+        indexed:
+            - !Command (with $index)
+        keyed:
+            xyz: !Command (with $key instead of $name)
+        plain:
+            xyz: !Command (without $name)
+    """
+
+    @classmethod
+    def header(cls) -> str:
+        return "commands"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('indexed'): [DocReference(Command)],
+                Optional('keyed'): {str: DocReference(Command)},
+                Optional('plain'): {str: DocReference(Command)},
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return [
+            ("indexed[]", Command, {"index_key": "$index"}),
+            ("keyed[]", Command, {"name_key": "$key"}),
+            ("plain[]", Command, {"name_key": None}),
+        ]
+
+
+class Command(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "command"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$index'): int,
+                Optional('$key'): str,
+                'run': str,
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class Renamed(YamlConfigDocument):
+    """
+    Renamed test document, whose documents may also have the legacy header "legacy". Example:
+
+    renamed:
+        $ref: /path
+        value: any
+    """
+
+    @classmethod
+    def header(cls) -> str:
+        return "renamed"
+
+    @classmethod
+    def header_aliases(cls) -> List[str]:
+        return ["legacy"]
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$ref'): str,
+                Optional('value'): lambda any: True,
+                Optional('more'): lambda any: True,
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class Plain(YamlConfigDocument):
+    """
+    Plain test document, which may be stored without a header. Example:
+
+    $ref: /path
+    value: any
+    """
+
+    @classmethod
+    def header(cls) -> str:
+        return "plain"
+
+    @classmethod
+    def headerless(cls) -> bool:
+        return True
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('$ref'): str,
+                Optional('value'): lambda any: True,
+                Optional('more'): lambda any: True,
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class StrictKeysBase(Base):
+    """
+    Base test document, whose keys must be strings.
+    """
+
+    @classmethod
+    def reject_non_string_keys(cls) -> bool:
+        return True
+
+
+class Yaml11Base(Base):
+    """
+    Base test document, that is loaded with YAML 1.1 booleans.
+    """
+
+    @classmethod
+    def yaml11_booleans(cls) -> bool:
+        return True